@@ -9,16 +9,96 @@ pub use libremarkable::framebuffer::{
     common::DISPLAYHEIGHT,
     common::color,
 };
-use libremarkable::framebuffer::{common::waveform_mode, common::display_temp, common::dither_mode, PartialRefreshMode};
+mod text;
+
+pub use text::{ascent, measure_text, Align, TextBox};
+
+use libremarkable::framebuffer::{common::waveform_mode, common::display_temp, common::dither_mode, PartialRefreshMode, FramebufferIO};
+use libremarkable::framebuffer::draw::DEFAULT_FONT;
+use libremarkable::image::{Rgb, RgbImage};
+use rusttype::{point, Scale};
+use std::fmt::Display;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Colors everything gets drawn in.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Theme {
+    /// Black on white
+    #[default]
+    Normal,
+    /// White on black
+    Inverted,
+}
+
+impl Theme {
+    /// Text, borders and blocks
+    pub fn foreground(self) -> color {
+        match self {
+            Theme::Normal => color::BLACK,
+            Theme::Inverted => color::WHITE,
+        }
+    }
+
+    /// Empty space
+    pub fn background(self) -> color {
+        match self {
+            Theme::Normal => color::WHITE,
+            Theme::Inverted => color::BLACK,
+        }
+    }
+
+    /// The other one (e.g. for a pressed button).
+    pub fn inverted(self) -> Self {
+        match self {
+            Theme::Normal => Theme::Inverted,
+            Theme::Inverted => Theme::Normal,
+        }
+    }
+
+    /// Gray that is `level` away from the background (255 is the foreground).
+    pub fn gray(self, level: u8) -> color {
+        match self {
+            Theme::Normal => color::GRAY(level),
+            Theme::Inverted => color::GRAY(255 - level),
+        }
+    }
+}
+
+impl FromStr for Theme {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "normal" => Ok(Theme::Normal),
+            "inverted" => Ok(Theme::Inverted),
+            _ => Err(format!("Unknown theme \"{}\". Expected normal or inverted", value)),
+        }
+    }
+}
+
+impl Display for Theme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Theme::Normal => write!(f, "normal"),
+            Theme::Inverted => write!(f, "inverted"),
+        }
+    }
+}
 
 pub struct Canvas {
     framebuffer: Box<Framebuffer>,
+    theme: Theme,
+    /// Refreshes issued since the last take_refresh_count()
+    refresh_count: u32,
 }
 
 impl Canvas {
     pub fn new() -> Self {
         Self {
             framebuffer: Box::new(Framebuffer::new()),
+            theme: Theme::Normal,
+            refresh_count: 0,
         }
     }
 
@@ -26,11 +106,55 @@ impl Canvas {
         self.framebuffer.as_mut()
     }
 
+    /// Amount of refreshes since the last call (for --debug).
+    pub fn take_refresh_count(&mut self) -> u32 {
+        std::mem::take(&mut self.refresh_count)
+    }
+
+    pub fn theme(&self) -> Theme {
+        self.theme
+    }
+
+    /// Only affects what gets drawn afterwards.
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+    }
+
     pub fn clear(&mut self) {
         self.framebuffer_mut().clear();
+        if self.theme.background() != color::WHITE {
+            self.fill_background(Point2 { x: 0, y: 0 }, Vector2 { x: DISPLAYWIDTH as u32, y: DISPLAYHEIGHT as u32 });
+        }
+    }
+
+    pub fn fill_background(&mut self, pos: Point2<i32>, size: Vector2<u32>) {
+        let background = self.theme.background();
+        self.framebuffer_mut().fill_rect(pos, size, background);
+    }
+
+    pub fn fill_foreground(&mut self, pos: Point2<i32>, size: Vector2<u32>) {
+        let foreground = self.theme.foreground();
+        self.framebuffer_mut().fill_rect(pos, size, foreground);
+    }
+
+    /// Draws a black and white image (e.g. a block texture)
+    /// with the colors of the theme.
+    pub fn draw_image(&mut self, image: &RgbImage, pos: Point2<i32>) -> mxcfb_rect {
+        if self.theme == Theme::Normal {
+            return self.framebuffer_mut().draw_image(image, pos);
+        }
+        for (x, y, pixel) in image.enumerate_pixels() {
+            let [r, g, b] = pixel.0;
+            self.framebuffer_mut().write_pixel(
+                Point2 { x: pos.x + x as i32, y: pos.y + y as i32 },
+                color::RGB(255 - r, 255 - g, 255 - b)
+            );
+        }
+        mxcfb_rect { top: pos.y as u32, left: pos.x as u32, width: image.width(), height: image.height() }
     }
 
     pub fn update_full(&mut self) {
+        self.refresh_count += 1;
         self.framebuffer_mut().full_refresh(
             waveform_mode::WAVEFORM_MODE_GC16,
             display_temp::TEMP_USE_REMARKABLE_DRAW,
@@ -40,7 +164,23 @@ impl Canvas {
         );
     }
 
+    /// Flashing refresh of only the given region. Slow but
+    /// leaves no ghosting behind.
+    pub fn update_partial_clean(&mut self, region: &mxcfb_rect) {
+        self.refresh_count += 1;
+        self.framebuffer_mut().partial_refresh(
+            region,
+            PartialRefreshMode::Async,
+            waveform_mode::WAVEFORM_MODE_GC16,
+            display_temp::TEMP_USE_REMARKABLE_DRAW,
+            dither_mode::EPDC_FLAG_USE_REMARKABLE_DITHER,
+            0,
+            false
+        );
+    }
+
     pub fn update_partial(&mut self, region: &mxcfb_rect) {
+        self.refresh_count += 1;
         self.framebuffer_mut().partial_refresh(
             region,
             PartialRefreshMode::Async,
@@ -53,6 +193,7 @@ impl Canvas {
     }
 
     pub fn update_partial_mono(&mut self, region: &mxcfb_rect) {
+        self.refresh_count += 1;
         self.framebuffer_mut().partial_refresh(
             region,
             PartialRefreshMode::Async,
@@ -67,22 +208,62 @@ impl Canvas {
     pub fn draw_text(&mut self, pos: Point2<Option<i32>>, text: &str, size: f32) -> mxcfb_rect {
         let mut pos = pos;
         if pos.x.is_none() || pos.y.is_none() {
-            // Do dryrun to get text size
-            let rect = self.framebuffer_mut().draw_text(Point2 { x: 0.0, y: DISPLAYHEIGHT as f32 }, &text, size, color::BLACK, true);
+            let text_size = measure_text(text, size);
 
             if pos.x.is_none() {
                 // Center horizontally
-                pos.x = Some(DISPLAYWIDTH as i32 / 2 - rect.width as i32 / 2);
+                pos.x = Some(DISPLAYWIDTH as i32 / 2 - text_size.x as i32 / 2);
             }
             
             if pos.y.is_none() {
                 // Center vertically
-                pos.y = Some(DISPLAYHEIGHT as i32 / 2 - rect.height as i32 / 2);
+                pos.y = Some(DISPLAYHEIGHT as i32 / 2 - text_size.y as i32 / 2);
             }
         }
         let pos = Point2 { x: pos.x.unwrap() as f32, y: pos.y.unwrap() as f32 };
 
-        self.framebuffer_mut().draw_text(pos, &text, size, color::BLACK, false)
+        self.render_text(pos, text, size)
+    }
+
+    /// Draws the text wrapped and aligned inside the box.
+    /// Returns the area that was drawn on.
+    pub fn draw_text_box(&mut self, text_box: &TextBox, text: &str) -> mxcfb_rect {
+        let mut drawn: Option<mxcfb_rect> = None;
+        for (line, pos) in text_box.layout(text) {
+            let rect = self.render_text(Point2 { x: pos.x as f32, y: pos.y as f32 }, &line, text_box.size);
+            drawn = Some(drawn.map_or(rect, |drawn| drawn.merge_rect(&rect)));
+        }
+        drawn.unwrap_or(mxcfb_rect { width: 0, height: 0, ..text_box.rect })
+    }
+
+    /// Like Framebuffer::draw_text but blends into the background
+    /// of the theme (that one only works for dark text).
+    fn render_text(&mut self, pos: Point2<f32>, text: &str, size: f32) -> mxcfb_rect {
+        let foreground = self.theme.foreground().to_rgb8();
+        let background = self.theme.background().to_rgb8();
+        let mut min = Point2 { x: pos.x.floor().max(0.0) as u32, y: pos.y.floor().max(0.0) as u32 };
+        let mut max = Point2 { x: pos.x.ceil().max(0.0) as u32, y: pos.y.ceil().max(0.0) as u32 };
+
+        for glyph in DEFAULT_FONT.layout(text, Scale::uniform(size), point(pos.x, pos.y)) {
+            let bounding_box = match glyph.pixel_bounding_box() {
+                Some(bounding_box) => bounding_box,
+                None => continue,
+            };
+            min.x = min.x.min(bounding_box.min.x.max(0) as u32);
+            min.y = min.y.min(bounding_box.min.y.max(0) as u32);
+            max.x = max.x.max(bounding_box.max.x.max(0) as u32);
+            max.y = max.y.max(bounding_box.max.y.max(0) as u32);
+
+            glyph.draw(|x, y, coverage| {
+                let mix = |i: usize| (background[i] as f32 + (foreground[i] as f32 - background[i] as f32) * coverage.min(1.0)) as u8;
+                self.framebuffer.write_pixel(
+                    Point2 { x: bounding_box.min.x + x as i32, y: bounding_box.min.y + y as i32 },
+                    color::RGB(mix(0), mix(1), mix(2))
+                );
+            });
+        }
+
+        mxcfb_rect { top: min.y, left: min.x, width: max.x - min.x, height: max.y - min.y }
     }
 
     pub fn draw_rect(&mut self, pos: Point2<Option<i32>>, size: Vector2<u32>, border_px: u32,) -> mxcfb_rect {
@@ -100,7 +281,8 @@ impl Canvas {
         }
         let pos = Point2 { x: pos.x.unwrap(), y: pos.y.unwrap() };
 
-        self.framebuffer_mut().draw_rect(pos, size, border_px, color::BLACK);
+        let foreground = self.theme.foreground();
+        self.framebuffer_mut().draw_rect(pos, size, border_px, foreground);
         mxcfb_rect {
             top: pos.y as u32,
             left: pos.x as u32,
@@ -109,13 +291,17 @@ impl Canvas {
         }
     }
 
-    pub fn draw_button(&mut self, pos: Point2<Option<i32>>, text: &str, font_size: f32, vgap: u32, hgap: u32) -> mxcfb_rect {
-        let text_rect = self.draw_text(pos, text, font_size);
-        self.draw_rect(
-            Point2 { x: Some((text_rect.left - hgap) as i32), y: Some((text_rect.top - vgap) as i32) }, 
-            Vector2 { x: hgap + text_rect.width + hgap, y: vgap + text_rect.height + vgap },
-            5
-        )
+    /// Saves everything that is currently in the framebuffer
+    /// as image. The format depends on the file extension.
+    pub fn save_image(&mut self, path: &Path) -> Result<(), String> {
+        let (width, height) = (DISPLAYWIDTH as u32, DISPLAYHEIGHT as u32);
+        let data = self.framebuffer_mut().dump_region(mxcfb_rect { left: 0, top: 0, width, height })?;
+        // Pixels are rgb565
+        let image = RgbImage::from_fn(width, height, |x, y| {
+            let index = ((y * width + x) * 2) as usize;
+            Rgb(color::from_native([data[index], data[index + 1]]).to_rgb8())
+        });
+        image.save(path).map_err(|err| err.to_string())
     }
 
     pub fn is_hitting(pos: Point2<u16>, hitbox: mxcfb_rect) -> bool {
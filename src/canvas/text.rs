@@ -0,0 +1,100 @@
+//! Measuring and laying out text, so screens don't need
+//! hand-computed pixel offsets.
+
+use super::{mxcfb_rect, Point2, Vector2};
+use libremarkable::framebuffer::draw::DEFAULT_FONT;
+use rusttype::{point, Scale};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Align {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+/// Size of a single line of text. The height goes from the highest
+/// ascender to the lowest descender of the font, so all lines of
+/// the same font size are equally high.
+pub fn measure_text(text: &str, size: f32) -> Vector2<u32> {
+    let scale = Scale::uniform(size);
+    let v_metrics = DEFAULT_FONT.v_metrics(scale);
+    let width = DEFAULT_FONT.layout(text, scale, point(0.0, 0.0))
+        .last()
+        .map(|glyph| glyph.position().x + glyph.unpositioned().h_metrics().advance_width)
+        .unwrap_or(0.0);
+    Vector2 { x: width.ceil() as u32, y: (v_metrics.ascent - v_metrics.descent).ceil() as u32 }
+}
+
+/// Distance from the top of a line to its baseline.
+pub fn ascent(size: f32) -> u32 {
+    DEFAULT_FONT.v_metrics(Scale::uniform(size)).ascent.ceil() as u32
+}
+
+/// Splits the text into lines that fit into the width. Newlines are
+/// kept and a word that is too long on its own gets a line for itself.
+pub fn wrap_text(text: &str, size: f32, max_width: u32) -> Vec<String> {
+    let mut lines = vec![];
+    for paragraph in text.split('\n') {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            let extended = if line.is_empty() { word.to_owned() } else { format!("{} {}", line, word) };
+            if line.is_empty() || measure_text(&extended, size).x <= max_width {
+                line = extended;
+            }else {
+                lines.push(std::mem::replace(&mut line, word.to_owned()));
+            }
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// Area to draw (possibly multiple lines of) text into.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TextBox {
+    pub rect: mxcfb_rect,
+    pub size: f32,
+    pub align: Align,
+    /// Distance between two baselines as multiple of the font size.
+    pub line_spacing: f32,
+}
+
+impl TextBox {
+    pub fn new(rect: mxcfb_rect, size: f32) -> Self {
+        Self { rect, size, align: Align::Left, line_spacing: 1.2 }
+    }
+
+    pub fn with_align(mut self, align: Align) -> Self {
+        self.align = align;
+        self
+    }
+
+    pub fn with_line_spacing(mut self, line_spacing: f32) -> Self {
+        self.line_spacing = line_spacing;
+        self
+    }
+
+    /// Wrapped lines of the text with the position of their baseline.
+    /// Lines that don't fit below each other anymore are still included.
+    pub fn layout(&self, text: &str) -> Vec<(String, Point2<i32>)> {
+        let line_height = (self.size * self.line_spacing).round() as i32;
+        let first_baseline = self.rect.top as i32 + ascent(self.size) as i32;
+        wrap_text(text, self.size, self.rect.width)
+            .into_iter()
+            .enumerate()
+            .map(|(index, line)| {
+                let free = self.rect.width.saturating_sub(measure_text(&line, self.size).x) as i32;
+                let x = self.rect.left as i32 + match self.align {
+                    Align::Left => 0,
+                    Align::Center => free / 2,
+                    Align::Right => free,
+                };
+                (line, Point2 { x, y: first_baseline + index as i32 * line_height })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests;
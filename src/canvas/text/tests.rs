@@ -0,0 +1,62 @@
+use super::*;
+
+const SIZE: f32 = 40.0;
+
+fn rect(width: u32) -> mxcfb_rect {
+    mxcfb_rect { left: 100, top: 200, width, height: 500 }
+}
+
+#[test]
+fn measure_grows_with_text() {
+    assert_eq!(measure_text("", SIZE).x, 0);
+    assert!(measure_text("ab", SIZE).x > measure_text("a", SIZE).x);
+    assert!(measure_text("a", 2.0 * SIZE).x > measure_text("a", SIZE).x);
+    // Same height no matter which letters
+    assert_eq!(measure_text("ag", SIZE).y, measure_text("ab", SIZE).y);
+}
+
+#[test]
+fn wrap_keeps_fitting_text() {
+    assert_eq!(wrap_text("Clear a line", SIZE, 1000), vec!["Clear a line"]);
+}
+
+#[test]
+fn wrap_splits_at_words() {
+    let text = "Clear the whole board with the pieces given";
+    let width = measure_text("Clear the whole", SIZE).x;
+    let lines = wrap_text(text, SIZE, width);
+    assert!(lines.len() > 1);
+    assert_eq!(lines.join(" "), text);
+    for line in lines {
+        assert!(measure_text(&line, SIZE).x <= width, "{} is too wide", line);
+    }
+}
+
+#[test]
+fn wrap_keeps_newlines() {
+    assert_eq!(wrap_text("Score\n\n100", SIZE, 1000), vec!["Score", "", "100"]);
+}
+
+#[test]
+fn wrap_gives_long_words_their_own_line() {
+    let width = measure_text("T-Spin", SIZE).x;
+    assert_eq!(wrap_text("a T-Spin-Double b", SIZE, width), vec!["a", "T-Spin-Double", "b"]);
+}
+
+#[test]
+fn layout_aligns_lines() {
+    let width = measure_text("Hello", SIZE).x;
+    let left = TextBox::new(rect(500), SIZE).layout("Hello");
+    assert_eq!(left[0].1.x, 100);
+    let right = TextBox::new(rect(500), SIZE).with_align(Align::Right).layout("Hello");
+    assert_eq!(right[0].1.x, 100 + 500 - width as i32);
+    let center = TextBox::new(rect(500), SIZE).with_align(Align::Center).layout("Hello");
+    assert_eq!(center[0].1.x, 100 + (500 - width as i32) / 2);
+}
+
+#[test]
+fn layout_spaces_lines() {
+    let lines = TextBox::new(rect(500), SIZE).with_line_spacing(1.5).layout("One\nTwo");
+    assert_eq!(lines[0].1.y, 200 + ascent(SIZE) as i32);
+    assert_eq!(lines[1].1.y - lines[0].1.y, 60);
+}
@@ -0,0 +1,31 @@
+//! Local wall clock time. std only knows UTC.
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct LocalTime {
+    pub year: i32,
+    /// 1 - 12
+    pub month: i32,
+    /// 1 - 31
+    pub day: i32,
+    pub hour: i32,
+    pub minute: i32,
+    pub second: i32,
+}
+
+pub fn now() -> LocalTime {
+    unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut tm: libc::tm = std::mem::zeroed();
+        if libc::localtime_r(&now, &mut tm).is_null() {
+            return LocalTime::default();
+        }
+        LocalTime {
+            year: tm.tm_year + 1900,
+            month: tm.tm_mon + 1,
+            day: tm.tm_mday,
+            hour: tm.tm_hour,
+            minute: tm.tm_min,
+            second: tm.tm_sec,
+        }
+    }
+}
@@ -0,0 +1,330 @@
+//! Persistent settings stored as simple `key=value` lines.
+//!
+//! Unknown keys and invalid values are reported and ignored,
+//! so an outdated or broken file never prevents the game from starting.
+
+use crate::canvas::Theme;
+use crate::engine::LockDelayConfig;
+use crate::hold::HoldConfig;
+use crate::swipe::SwipeConfig;
+use crate::tap_zones::TapZonesConfig;
+use std::fmt::Display;
+use std::fs;
+use std::io;
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{LazyLock, PoisonError, RwLock};
+use std::time::Duration;
+
+/// How the player moves the blocks by touch.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub enum ControlScheme {
+    /// Swipe left, right and down
+    #[default]
+    Swipe,
+    /// Drag the block to the column below the finger
+    Drag,
+    /// Tap areas of the screen (see TapZonesConfig)
+    TapZones,
+}
+
+impl FromStr for ControlScheme {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "swipe" => Ok(ControlScheme::Swipe),
+            "drag" => Ok(ControlScheme::Drag),
+            "tap_zones" => Ok(ControlScheme::TapZones),
+            _ => Err("Expected swipe, drag or tap_zones".to_owned()),
+        }
+    }
+}
+
+impl Display for ControlScheme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ControlScheme::Swipe => write!(f, "swipe"),
+            ControlScheme::Drag => write!(f, "drag"),
+            ControlScheme::TapZones => write!(f, "tap_zones"),
+        }
+    }
+}
+
+/// Which waveform is used for refreshing changed blocks.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub enum RefreshPolicy {
+    /// Grayscale on the rM1 and black and white on the rM2
+    #[default]
+    Auto,
+    /// Black and white only. Fastest but leaves more ghosting.
+    Fast,
+    /// Grayscale. Shows the patterns better but is slower.
+    Quality,
+}
+
+impl FromStr for RefreshPolicy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "auto" => Ok(RefreshPolicy::Auto),
+            "fast" => Ok(RefreshPolicy::Fast),
+            "quality" => Ok(RefreshPolicy::Quality),
+            _ => Err("Expected auto, fast or quality".to_owned()),
+        }
+    }
+}
+
+impl Display for RefreshPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RefreshPolicy::Auto => write!(f, "auto"),
+            RefreshPolicy::Fast => write!(f, "fast"),
+            RefreshPolicy::Quality => write!(f, "quality"),
+        }
+    }
+}
+
+/// Read once at startup and updated by Config::save().
+static LOADED: LazyLock<RwLock<Loaded>> = LazyLock::new(|| {
+    let stored = Config::load();
+    let mut current = stored.clone();
+    for err in current.apply_overrides() {
+        eprintln!("{}", err);
+    }
+    RwLock::new(Loaded { stored, current })
+});
+
+struct Loaded {
+    /// As in the config file
+    stored: Config,
+    /// With the command line overrides
+    current: Config,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    /// Show the software « and » buttons
+    pub arrow_buttons: bool,
+    /// Show where the block would land
+    pub ghost_piece: bool,
+    /// Draw blocks with patterns instead of plain black
+    pub block_patterns: bool,
+    pub refresh_policy: RefreshPolicy,
+    pub theme: Theme,
+    pub control_scheme: ControlScheme,
+    pub swipe: SwipeConfig,
+    pub hold: HoldConfig,
+    pub tap_zones: TapZonesConfig,
+    pub lock_delay: LockDelayConfig,
+    /// Battery shown in the status bar (a sysfs power_supply directory).
+    /// Found automatically if not set.
+    pub power_supply_path: Option<PathBuf>,
+    /// Where screenshots are saved. Next to the config file if not set.
+    pub screenshot_dir: Option<PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            arrow_buttons: true,
+            ghost_piece: false,
+            block_patterns: true,
+            refresh_policy: RefreshPolicy::default(),
+            theme: Theme::default(),
+            control_scheme: ControlScheme::default(),
+            swipe: SwipeConfig::default(),
+            hold: HoldConfig::default(),
+            tap_zones: TapZonesConfig::default(),
+            lock_delay: LockDelayConfig::default(),
+            power_supply_path: None,
+            screenshot_dir: None,
+        }
+    }
+}
+
+impl Config {
+    /// Location of the config file. Can be changed with `--config`.
+    pub fn path() -> PathBuf {
+        if let Some(ref path) = crate::CLI_OPTS.config {
+            return path.clone();
+        }
+        let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+            .unwrap_or_else(|| PathBuf::from("/home/root/.config"));
+        config_dir.join("retris").join("retris.conf")
+    }
+
+    /// Loads the config file or returns the defaults if there is none.
+    fn load() -> Self {
+        match fs::read_to_string(Self::path()) {
+            Ok(contents) => Self::parse(&contents),
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => Self::default(),
+            Err(err) => {
+                eprintln!("Failed to read config file {:?}: {}", Self::path(), err);
+                Self::default()
+            },
+        }
+    }
+
+    pub fn screenshot_dir(&self) -> PathBuf {
+        self.screenshot_dir.clone().unwrap_or_else(|| Self::path().with_file_name("screenshots"))
+    }
+
+    /// The stored settings with the ones given on the command line
+    /// (`--set` and `--no-arrow-buttons`) taking precedence.
+    pub fn current() -> Self {
+        LOADED.read().unwrap_or_else(PoisonError::into_inner).current.clone()
+    }
+
+    /// The settings of the config file without the ones given
+    /// on the command line, e.g. for changing and saving them.
+    pub fn stored() -> Self {
+        LOADED.read().unwrap_or_else(PoisonError::into_inner).stored.clone()
+    }
+
+    /// Applies the settings given on the command line
+    /// and returns the ones that are invalid.
+    fn apply_overrides(&mut self) -> Vec<String> {
+        if crate::CLI_OPTS.no_arrow_buttons {
+            self.arrow_buttons = false;
+        }
+        crate::CLI_OPTS.set.iter().filter_map(|setting| {
+            let result = match setting.split_once('=') {
+                Some((key, value)) => self.set(key.trim(), value.trim()),
+                None => Err(format!("Expected key=value but got \"{}\"", setting)),
+            };
+            result.err().map(|err| format!("Option --set {}: {}", setting, err))
+        }).collect()
+    }
+
+    /// Whether the key is given on the command line. Changing
+    /// it in the config file has no effect then.
+    pub fn is_overridden(key: &str) -> bool {
+        (key == "arrow_buttons" && crate::CLI_OPTS.no_arrow_buttons)
+            || crate::CLI_OPTS.set.iter().any(|setting| setting.split_once('=').map(|(k, _)| k.trim()) == Some(key))
+    }
+
+    /// Writes the config file. The settings apply from now on,
+    /// even if writing fails.
+    pub fn save(&self) -> io::Result<()> {
+        let mut current = self.clone();
+        // Invalid ones got reported when loading already
+        current.apply_overrides();
+        *LOADED.write().unwrap_or_else(PoisonError::into_inner) = Loaded { stored: self.clone(), current };
+
+        let path = Self::path();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, self.to_string())
+    }
+
+    pub fn parse(contents: &str) -> Self {
+        let mut config = Self::default();
+        config.apply(contents);
+        config
+    }
+
+    /// Changes the settings given in the contents of a config file.
+    fn apply(&mut self, contents: &str) {
+        for (line_number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => {
+                    eprintln!("Config line {} is missing a '=': {}", line_number + 1, line);
+                    continue;
+                },
+            };
+            if let Err(err) = self.set(key, value) {
+                eprintln!("Config line {}: {}", line_number + 1, err);
+            }
+        }
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "arrow_buttons" => self.arrow_buttons = parse_value(key, value)?,
+            "ghost_piece" => self.ghost_piece = parse_value(key, value)?,
+            "block_patterns" => self.block_patterns = parse_value(key, value)?,
+            "refresh_policy" => self.refresh_policy = parse_value(key, value)?,
+            "theme" => self.theme = parse_value(key, value)?,
+            "control_scheme" => self.control_scheme = parse_value(key, value)?,
+            // Same limits as in the calibration scene
+            "swipe_chunk_distance" => self.swipe.chunk_dist = parse_in_range(key, value, 5..=200)?,
+            "swipe_completed_min_chunk_count" => self.swipe.completed_min_chunk_count = parse_in_range(key, value, 1..=10)?,
+            "swipe_min_ratio" => self.swipe.min_ratio = parse_in_range(key, value, 1.0..=5.0)?,
+            "swipe_allow_multiple_swipes_at_once" => self.swipe.allow_multiple_swipes_at_once = parse_value(key, value)?,
+            "hold_das_ms" => self.hold.das = Duration::from_millis(parse_in_range(key, value, 50..=2000)?),
+            "hold_arr_ms" => self.hold.arr = Duration::from_millis(parse_in_range(key, value, 10..=1000)?),
+            "tap_zone_side_width" => self.tap_zones.side_width = parse_in_range(key, value, 0.0..=0.5)?,
+            "tap_zone_drop_height" => self.tap_zones.drop_height = parse_in_range(key, value, 0.0..=1.0)?,
+            "tap_zone_outlines" => self.tap_zones.show_outlines = parse_value(key, value)?,
+            "lock_delay_ms" => self.lock_delay.delay = Duration::from_millis(parse_in_range(key, value, 100..=5000)?),
+            "lock_delay_max_resets" => self.lock_delay.max_resets = parse_value(key, value)?,
+            "power_supply_path" => self.power_supply_path = Some(parse_path(key, value)?),
+            "screenshot_dir" => self.screenshot_dir = Some(parse_path(key, value)?),
+            _ => return Err(format!("Unknown key \"{}\"", key)),
+        }
+        Ok(())
+    }
+}
+
+impl Display for Config {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "# reTris config")?;
+        writeln!(f, "arrow_buttons={}", self.arrow_buttons)?;
+        writeln!(f, "ghost_piece={}", self.ghost_piece)?;
+        writeln!(f, "block_patterns={}", self.block_patterns)?;
+        writeln!(f, "refresh_policy={}", self.refresh_policy)?;
+        writeln!(f, "theme={}", self.theme)?;
+        writeln!(f, "control_scheme={}", self.control_scheme)?;
+        writeln!(f, "swipe_chunk_distance={}", self.swipe.chunk_dist)?;
+        writeln!(f, "swipe_completed_min_chunk_count={}", self.swipe.completed_min_chunk_count)?;
+        writeln!(f, "swipe_min_ratio={}", self.swipe.min_ratio)?;
+        writeln!(f, "swipe_allow_multiple_swipes_at_once={}", self.swipe.allow_multiple_swipes_at_once)?;
+        writeln!(f, "hold_das_ms={}", self.hold.das.as_millis())?;
+        writeln!(f, "hold_arr_ms={}", self.hold.arr.as_millis())?;
+        writeln!(f, "tap_zone_side_width={}", self.tap_zones.side_width)?;
+        writeln!(f, "tap_zone_drop_height={}", self.tap_zones.drop_height)?;
+        writeln!(f, "tap_zone_outlines={}", self.tap_zones.show_outlines)?;
+        writeln!(f, "lock_delay_ms={}", self.lock_delay.delay.as_millis())?;
+        writeln!(f, "lock_delay_max_resets={}", self.lock_delay.max_resets)?;
+        if let Some(ref power_supply_path) = self.power_supply_path {
+            writeln!(f, "power_supply_path={}", power_supply_path.display())?;
+        }
+        if let Some(ref screenshot_dir) = self.screenshot_dir {
+            writeln!(f, "screenshot_dir={}", screenshot_dir.display())?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_value<T: FromStr>(key: &str, value: &str) -> Result<T, String> where T::Err: Display {
+    value.parse::<T>().map_err(|err| format!("Invalid value \"{}\" for {}: {}", value, key, err))
+}
+
+fn parse_path(key: &str, value: &str) -> Result<PathBuf, String> {
+    if value.is_empty() {
+        return Err(format!("Missing path for {}", key));
+    }
+    Ok(PathBuf::from(value))
+}
+
+fn parse_in_range<T: FromStr + PartialOrd + Display>(key: &str, value: &str, range: RangeInclusive<T>) -> Result<T, String> where T::Err: Display {
+    let parsed = parse_value(key, value)?;
+    if !range.contains(&parsed) {
+        return Err(format!("Invalid value \"{}\" for {}: Must be between {} and {}", value, key, range.start(), range.end()));
+    }
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod tests;
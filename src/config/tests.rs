@@ -0,0 +1,111 @@
+use super::*;
+
+/// Config::default() needs to know the device, so
+/// everything is tested against this one instead.
+fn fixed_config() -> Config {
+    Config {
+        arrow_buttons: true,
+        ghost_piece: false,
+        block_patterns: true,
+        refresh_policy: RefreshPolicy::Auto,
+        theme: Theme::Normal,
+        control_scheme: ControlScheme::Swipe,
+        swipe: SwipeConfig::default(),
+        hold: HoldConfig::default(),
+        tap_zones: TapZonesConfig::default(),
+        lock_delay: LockDelayConfig::GUIDELINE,
+        power_supply_path: None,
+        screenshot_dir: None,
+    }
+}
+
+/// Differs from fixed_config() in every setting.
+fn changed_config() -> Config {
+    Config {
+        arrow_buttons: false,
+        ghost_piece: true,
+        block_patterns: false,
+        refresh_policy: RefreshPolicy::Quality,
+        theme: Theme::Inverted,
+        control_scheme: ControlScheme::TapZones,
+        swipe: SwipeConfig { chunk_dist: 40, completed_min_chunk_count: 3, min_ratio: 2.5, allow_multiple_swipes_at_once: false },
+        hold: HoldConfig { das: Duration::from_millis(300), arr: Duration::from_millis(50) },
+        tap_zones: TapZonesConfig { side_width: 0.25, drop_height: 0.2, show_outlines: false },
+        lock_delay: LockDelayConfig { delay: Duration::from_millis(900), max_resets: 5 },
+        power_supply_path: Some(PathBuf::from("/sys/class/power_supply/battery")),
+        screenshot_dir: Some(PathBuf::from("/home/root/screenshots")),
+    }
+}
+
+#[test]
+fn saved_config_reads_back_the_same() {
+    let changed = changed_config();
+    let mut config = fixed_config();
+    config.apply(&changed.to_string());
+    assert_eq!(config, changed);
+
+    // Unset paths aren't written at all
+    let mut config = fixed_config();
+    config.apply(&fixed_config().to_string());
+    assert_eq!(config, fixed_config());
+}
+
+#[test]
+fn comments_and_blank_lines_are_skipped() {
+    let mut config = fixed_config();
+    config.apply("# ghost_piece=true\n\n  ghost_piece = true  \nno equals sign\n");
+    assert_eq!(config, Config { ghost_piece: true, ..fixed_config() });
+}
+
+#[test]
+fn every_key_rejects_invalid_values() {
+    let invalid = [
+        ("arrow_buttons", vec!["yes", ""]),
+        ("ghost_piece", vec!["1"]),
+        ("block_patterns", vec!["on"]),
+        ("refresh_policy", vec!["slow"]),
+        ("theme", vec!["dark"]),
+        ("control_scheme", vec!["tap"]),
+        ("swipe_chunk_distance", vec!["4", "201", "-5", "70000", "far"]),
+        ("swipe_completed_min_chunk_count", vec!["0", "11"]),
+        ("swipe_min_ratio", vec!["0.5", "5.5", "NaN"]),
+        ("swipe_allow_multiple_swipes_at_once", vec!["maybe"]),
+        ("hold_das_ms", vec!["0", "49", "2001", "-1"]),
+        ("hold_arr_ms", vec!["0", "9", "1001"]),
+        ("tap_zone_side_width", vec!["-0.1", "0.6", "wide"]),
+        ("tap_zone_drop_height", vec!["-0.1", "1.5"]),
+        ("tap_zone_outlines", vec!["shown"]),
+        ("lock_delay_ms", vec!["0", "99", "5001", "99999999999999999999"]),
+        ("lock_delay_max_resets", vec!["-1", "many"]),
+        ("power_supply_path", vec![""]),
+        ("screenshot_dir", vec![""]),
+    ];
+    // Every key written by Display is covered
+    let keys: Vec<String> = changed_config().to_string().lines()
+        .filter_map(|line| line.split_once('=').map(|(key, _)| key.to_owned()))
+        .collect();
+    assert_eq!(keys, invalid.iter().map(|(key, _)| key.to_string()).collect::<Vec<_>>());
+
+    for (key, values) in invalid.iter() {
+        for value in values {
+            let mut config = fixed_config();
+            assert!(config.set(key, value).is_err(), "{}={} got accepted", key, value);
+            assert_eq!(config, fixed_config(), "{}={} changed the config", key, value);
+        }
+    }
+}
+
+#[test]
+fn values_at_the_limits_are_accepted() {
+    let mut config = fixed_config();
+    for (key, value) in [("swipe_chunk_distance", "5"), ("swipe_chunk_distance", "200"), ("hold_das_ms", "50"),
+            ("hold_arr_ms", "1000"), ("lock_delay_ms", "100"), ("lock_delay_ms", "5000"), ("tap_zone_side_width", "0.5")] {
+        assert_eq!(config.set(key, value), Ok(()), "{}={}", key, value);
+    }
+    assert_eq!(config.lock_delay.delay, Duration::from_millis(5000));
+}
+
+#[test]
+fn unknown_keys_are_rejected() {
+    assert!(fixed_config().set("ghost", "true").is_err());
+}
@@ -0,0 +1,154 @@
+//! Metrics for finding out why the game lags (`--debug` and `--debug-log`).
+//!
+//! Per frame this measures how long drawing took, how many refreshes
+//! were issued, how long it took from receiving input until a refresh
+//! and how many input events were waiting. The overlay shows them at
+//! the top left, the log gets one line per frame:
+//!
+//! ```text
+//! <ms since start> <frame time in µs> <refreshes> <latency in µs or -> <queued events>
+//! ```
+
+use crate::canvas::*;
+use libremarkable::input::InputEvent;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex, mpsc::{channel, Receiver}};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The overlay needs a refresh itself, so don't do that every frame.
+const OVERLAY_INTERVAL: Duration = Duration::from_millis(500);
+const FONT_SIZE: f32 = 30.0;
+const AREA: mxcfb_rect = mxcfb_rect { left: 50, top: 110, width: 900, height: 45 };
+
+/// When each input event arrived, in the same order as the events.
+#[derive(Clone, Default)]
+pub struct InputTimes(Arc<Mutex<VecDeque<Instant>>>);
+
+impl InputTimes {
+    /// Arrival of the next event taken out of the channel.
+    pub fn pop(&self) -> Option<Instant> {
+        self.0.lock().unwrap().pop_front()
+    }
+}
+
+/// Puts a thread in between that notes when each event arrived.
+/// Otherwise the time events spent waiting in the channel is unknown.
+pub fn timestamp_input(input_rx: Receiver<InputEvent>) -> (Receiver<InputEvent>, InputTimes) {
+    let (tx, rx) = channel();
+    let times = InputTimes::default();
+    let thread_times = times.clone();
+    thread::spawn(move || {
+        for event in input_rx {
+            // Before sending, so it's always there when the event is taken
+            thread_times.0.lock().unwrap().push_back(Instant::now());
+            if tx.send(event).is_err() {
+                break;
+            }
+        }
+    });
+    (rx, times)
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct FrameMetrics {
+    /// Handling input and drawing
+    pub frame_time: Duration,
+    pub refreshes: u32,
+    /// From receiving the first input event of the frame until
+    /// the end of drawing. Only set if there was input and a refresh.
+    pub latency: Option<Duration>,
+    /// Input events handled in this frame
+    pub queue_depth: usize,
+}
+
+pub struct DebugOverlay {
+    show: bool,
+    log: Option<BufWriter<File>>,
+    started: Instant,
+    last_drawn: Option<Instant>,
+    /// Frames since the overlay was drawn last
+    frames: Vec<FrameMetrics>,
+    /// Last latency that was measured (kept for frames without input)
+    last_latency: Option<Duration>,
+}
+
+impl DebugOverlay {
+    pub fn new(show: bool, log_path: Option<&Path>) -> io::Result<Self> {
+        let log = match log_path {
+            Some(path) => {
+                let mut log = BufWriter::new(File::create(path)?);
+                writeln!(log, "# ms frame_us refreshes latency_us queue")?;
+                Some(log)
+            },
+            None => None,
+        };
+        Ok(Self { show, log, started: Instant::now(), last_drawn: None, frames: vec![], last_latency: None })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.show || self.log.is_some()
+    }
+
+    pub fn record(&mut self, metrics: FrameMetrics) {
+        if let Some(ref mut log) = self.log {
+            let latency = metrics.latency.map(|latency| latency.as_micros().to_string()).unwrap_or_else(|| "-".to_owned());
+            let result = writeln!(log, "{} {} {} {} {}",
+                self.started.elapsed().as_millis(),
+                metrics.frame_time.as_micros(),
+                metrics.refreshes,
+                latency,
+                metrics.queue_depth
+            ).and_then(|_| log.flush());
+            if let Err(err) = result {
+                eprintln!("Failed to write debug log: {}", err);
+                self.log = None;
+            }
+        }
+        if metrics.latency.is_some() {
+            self.last_latency = metrics.latency;
+        }
+        if self.show {
+            self.frames.push(metrics);
+        }
+    }
+
+    /// Draws the averages since the last time, if it is due.
+    pub fn draw(&mut self, canvas: &mut Canvas) {
+        if !self.show || self.frames.is_empty() || self.last_drawn.map(|last| last.elapsed() < OVERLAY_INTERVAL).unwrap_or(false) {
+            return;
+        }
+        self.last_drawn = Some(Instant::now());
+
+        let frames = std::mem::take(&mut self.frames);
+        let count = frames.len() as u32;
+        let average_frame = frames.iter().map(|frame| frame.frame_time).sum::<Duration>() / count;
+        let max_frame = frames.iter().map(|frame| frame.frame_time).max().unwrap_or_default();
+        let refreshes: u32 = frames.iter().map(|frame| frame.refreshes).sum();
+        let max_queue = frames.iter().map(|frame| frame.queue_depth).max().unwrap_or(0);
+        let latency = match self.last_latency {
+            Some(latency) => format!("{} ms", latency.as_millis()),
+            None => "-".to_owned(),
+        };
+        let text = format!(
+            "Frame {:.1} ms (max {}) | Refreshes {} | Latency {} | Queue {}",
+            average_frame.as_secs_f64() * 1000.0,
+            max_frame.as_millis(),
+            refreshes,
+            latency,
+            max_queue
+        );
+
+        canvas.fill_background(
+            Point2 { x: AREA.left as i32, y: AREA.top as i32 },
+            Vector2 { x: AREA.width, y: AREA.height }
+        );
+        canvas.draw_text(Point2 { x: Some(AREA.left as i32), y: Some(AREA.top as i32 + 35) }, &text, FONT_SIZE);
+        canvas.update_partial_mono(&AREA);
+        // Not caused by the scene
+        canvas.take_refresh_count();
+    }
+}
@@ -0,0 +1,94 @@
+use super::piece::{Piece, PieceKind};
+
+/// Content of a filled cell.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Cell {
+    Piece(PieceKind),
+    /// Sent by the opponent in versus mode.
+    Garbage,
+}
+
+/// All locked cells. Row 0 is at the top.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Board {
+    width: usize,
+    height: usize,
+    cells: Vec<Option<Cell>>,
+}
+
+impl Board {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self { width, height, cells: vec![None; width * height] }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The cell at the given position. Everything
+    /// outside the board counts as filled.
+    pub fn is_filled(&self, x: i32, y: i32) -> bool {
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            return true;
+        }
+        self.cells[y as usize * self.width + x as usize].is_some()
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<Cell> {
+        self.cells[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, cell: Option<Cell>) {
+        self.cells[y * self.width + x] = cell;
+    }
+
+    pub fn collides(&self, piece: &Piece) -> bool {
+        piece.cells().iter().any(|(x, y)| self.is_filled(*x, *y))
+    }
+
+    pub fn lock(&mut self, piece: &Piece) {
+        for (x, y) in piece.cells() {
+            if !self.is_filled(x, y) {
+                self.set(x as usize, y as usize, Some(Cell::Piece(piece.kind)));
+            }
+        }
+    }
+
+    fn is_row_full(&self, y: usize) -> bool {
+        (0..self.width).all(|x| self.get(x, y).is_some())
+    }
+
+    /// Removes all full rows and returns how many there were.
+    pub fn clear_full_rows(&mut self) -> usize {
+        let full_rows: Vec<usize> = (0..self.height).filter(|y| self.is_row_full(*y)).collect();
+        for y in full_rows.iter() {
+            // Move everything above one row down
+            self.cells.drain(y * self.width..(y + 1) * self.width);
+            self.cells.splice(0..0, vec![None; self.width]);
+        }
+        full_rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.iter().all(|cell| cell.is_none())
+    }
+
+    /// Pushes everything up and adds filled rows at the bottom
+    /// which have a hole in the given column. Returns false
+    /// if any filled cell got pushed out at the top.
+    pub fn add_garbage_rows(&mut self, count: usize, hole: usize) -> bool {
+        let count = count.min(self.height);
+        let overflow = self.cells[..count * self.width].iter().any(|cell| cell.is_some());
+        self.cells.drain(..count * self.width);
+        for _ in 0..count {
+            for x in 0..self.width {
+                self.cells.push(if x == hole { None } else { Some(Cell::Garbage) });
+            }
+        }
+        !overflow
+    }
+}
@@ -0,0 +1,392 @@
+//! The game itself without any drawing or input handling.
+//!
+//! Replaces tetris_core which only knew a single rotation and
+//! didn't give access to the board. Rotations follow the
+//! Super Rotation System (see srs.rs).
+
+mod board;
+mod piece;
+mod srs;
+mod t_spin;
+
+pub use board::{Board, Cell};
+pub use piece::{Piece, PieceKind, RotationDirection};
+pub use t_spin::TSpin;
+
+use libremarkable::device::{CURRENT_DEVICE, Model};
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Time between two gravity steps at speed 1.0
+const MOVING_PERIOD: f64 = 0.2; // secs
+/// Amount of upcoming pieces that are known
+const QUEUE_SIZE: usize = 3;
+/// Points per cleared line
+const LINE_SCORE: u64 = 100;
+
+/// Source of all randomness in the game. Gets asked
+/// for a number between 0 and 6 for every new piece.
+pub trait Randomizer {
+    fn random_between(&self, first: i32, last: i32) -> i32;
+}
+
+/// How long a piece can still be moved after touching down.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct LockDelayConfig {
+    /// Time on the ground until the piece locks. Doesn't get
+    /// shorter with higher speeds, the screen isn't any faster.
+    pub delay: Duration,
+    /// How often moving or rotating on the ground (or landing again
+    /// after a kick lifted the piece) restarts the delay. Reaching a
+    /// new lowest row allows that many again.
+    pub max_resets: u32,
+}
+
+impl LockDelayConfig {
+    /// What most other tetris games use.
+    pub const GUIDELINE: Self = Self { delay: Duration::from_millis(500), max_resets: 15 };
+}
+
+impl Default for LockDelayConfig {
+    fn default() -> Self {
+        // Every move needs to be shown before the player can react
+        // on it. The rM1 takes noticeably longer for that.
+        let delay = match CURRENT_DEVICE.model {
+            Model::Gen1 => Duration::from_millis(800),
+            Model::Gen2 => Duration::from_millis(600),
+        };
+        Self { delay, ..Self::GUIDELINE }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Size {
+    pub width: usize,
+    pub height: usize,
+}
+
+/// A filled cell, either locked or of the active piece.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub x: usize,
+    pub y: usize,
+    pub cell: Cell,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    MoveDown,
+    RotateCw,
+    RotateCcw,
+    Rotate180,
+    /// Move all the way down and lock immediately.
+    HardDrop,
+}
+
+/// Result of a piece that locked as T-spin.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TSpinClear {
+    pub t_spin: TSpin,
+    pub lines: u64,
+}
+
+pub struct Game {
+    board: Board,
+    active: Piece,
+    queue: VecDeque<PieceKind>,
+    randomizer: Box<dyn Randomizer>,
+    /// Multiplier for gravity
+    speed: f64,
+    waiting_time: f64,
+    lock_delay: LockDelayConfig,
+    /// Time the active piece is on the ground already (None if falling).
+    lock_time: Option<f64>,
+    /// How often the lock delay started since reaching lowest_y.
+    /// The first start isn't a reset.
+    lock_starts: u32,
+    /// Lowest row the active piece was at. Used for resetting lock_starts.
+    lowest_y: i32,
+    /// Direction and used kick if the last move was a rotation.
+    last_rotation: Option<(RotationDirection, usize)>,
+    last_t_spin: Option<TSpinClear>,
+    score: u64,
+    lines_cleared: u64,
+    pieces_placed: u64,
+    pending_garbage: u32,
+    game_over: bool,
+}
+
+impl Game {
+    /// `speed` scales the gravity, 1.0 being the default.
+    pub fn new(size: &Size, speed: f64, lock_delay: LockDelayConfig, randomizer: Box<dyn Randomizer>) -> Self {
+        let mut queue = VecDeque::with_capacity(QUEUE_SIZE + 1);
+        for _ in 0..=QUEUE_SIZE {
+            queue.push_back(PieceKind::from_index(randomizer.random_between(0, 6)));
+        }
+        let first = queue.pop_front().unwrap();
+        Self {
+            board: Board::new(size.width, size.height),
+            active: Self::spawn_piece(first, size.width),
+            queue,
+            randomizer,
+            speed,
+            waiting_time: 0.0,
+            lock_delay,
+            lock_time: None,
+            lock_starts: 0,
+            lowest_y: 0,
+            last_rotation: None,
+            last_t_spin: None,
+            score: 0,
+            lines_cleared: 0,
+            pieces_placed: 0,
+            pending_garbage: 0,
+            game_over: false,
+        }
+    }
+
+    fn spawn_piece(kind: PieceKind, width: usize) -> Piece {
+        Piece::new(kind, width as i32 / 2 - 2, 0)
+    }
+
+    /// Takes effect with the next start of the lock delay.
+    pub fn set_lock_delay(&mut self, lock_delay: LockDelayConfig) {
+        self.lock_delay = lock_delay;
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    /// Replaces all locked cells, e.g. to start with a prepared board.
+    pub fn set_board(&mut self, board: Board) {
+        self.board = board;
+        self.game_over = self.board.collides(&self.active);
+    }
+
+    pub fn active(&self) -> &Piece {
+        &self.active
+    }
+
+    /// Where the active piece would land when dropped.
+    pub fn ghost(&self) -> Piece {
+        let mut ghost = self.active;
+        while !self.board.collides(&ghost.moved(0, 1)) {
+            ghost = ghost.moved(0, 1);
+        }
+        ghost
+    }
+
+    /// The next pieces in the order they will appear.
+    pub fn queue(&self) -> impl Iterator<Item = &PieceKind> {
+        self.queue.iter()
+    }
+
+    pub fn get_score(&self) -> u64 {
+        self.score
+    }
+
+    /// The last T-spin, if there was one since the last call.
+    pub fn take_t_spin(&mut self) -> Option<TSpinClear> {
+        self.last_t_spin.take()
+    }
+
+    pub fn lines_cleared(&self) -> u64 {
+        self.lines_cleared
+    }
+
+    /// Amount of pieces that were locked so far.
+    pub fn pieces_placed(&self) -> u64 {
+        self.pieces_placed
+    }
+
+    pub fn is_game_over(&self) -> bool {
+        self.game_over
+    }
+
+    /// All filled cells including the active piece.
+    pub fn blocks(&self) -> Vec<Block> {
+        let mut blocks = vec![];
+        for y in 0..self.board.height() {
+            for x in 0..self.board.width() {
+                if let Some(cell) = self.board.get(x, y) {
+                    blocks.push(Block { x, y, cell });
+                }
+            }
+        }
+        if !self.game_over {
+            for (x, y) in self.active.cells() {
+                if x >= 0 && y >= 0 {
+                    blocks.push(Block { x: x as usize, y: y as usize, cell: Cell::Piece(self.active.kind) });
+                }
+            }
+        }
+        blocks
+    }
+
+    /// Advances gravity and the lock delay by the given time (in secs).
+    pub fn update(&mut self, delta_time: f64) {
+        if self.game_over {
+            return;
+        }
+        if let Some(lock_time) = self.lock_time {
+            // On the ground. Gravity has nothing to do.
+            self.waiting_time = 0.0;
+            let lock_time = lock_time + delta_time;
+            if lock_time >= self.lock_delay.delay.as_secs_f64() {
+                self.lock();
+            }else {
+                self.lock_time = Some(lock_time);
+            }
+            return;
+        }
+
+        self.waiting_time += delta_time;
+        if self.waiting_time > self.moving_period() {
+            self.waiting_time = 0.0;
+            self.try_move(0, 1);
+        }
+    }
+
+    fn moving_period(&self) -> f64 {
+        MOVING_PERIOD / self.speed
+    }
+
+    /// Time (in secs) until update() would move or lock
+    /// the active piece. None if the game is over.
+    pub fn next_update_in(&self) -> Option<f64> {
+        if self.game_over {
+            return None;
+        }
+        let remaining = match self.lock_time {
+            Some(lock_time) => self.lock_delay.delay.as_secs_f64() - lock_time,
+            None => self.moving_period() - self.waiting_time,
+        };
+        Some(remaining.max(0.0))
+    }
+
+    fn is_on_ground(&self) -> bool {
+        self.board.collides(&self.active.moved(0, 1))
+    }
+
+    /// Starts, restarts or stops the lock delay
+    /// after the active piece changed.
+    fn on_active_changed(&mut self) {
+        if self.active.y > self.lowest_y {
+            self.lowest_y = self.active.y;
+            self.lock_starts = 0;
+        }
+        self.lock_time = if !self.is_on_ground() {
+            None
+        }else if self.lock_starts <= self.lock_delay.max_resets {
+            // Touched down the first time on this row or a reset is left
+            self.lock_starts += 1;
+            Some(0.0)
+        }else {
+            // No resets left. Keep the time running or, if the piece
+            // got lifted in between (e.g. by a kick), lock right away.
+            Some(self.lock_time.unwrap_or(self.lock_delay.delay.as_secs_f64()))
+        };
+    }
+
+    /// Returns whether the action could be done.
+    pub fn perform(&mut self, action: Action) -> bool {
+        if self.game_over {
+            return false;
+        }
+        match action {
+            Action::MoveLeft => self.try_move(-1, 0),
+            Action::MoveRight => self.try_move(1, 0),
+            Action::MoveDown => self.try_move(0, 1),
+            Action::RotateCw => self.try_rotate(RotationDirection::Cw),
+            Action::RotateCcw => self.try_rotate(RotationDirection::Ccw),
+            Action::Rotate180 => self.try_rotate(RotationDirection::Half),
+            Action::HardDrop => {
+                while self.try_move(0, 1) { }
+                self.lock();
+                true
+            },
+        }
+    }
+
+    fn try_move(&mut self, dx: i32, dy: i32) -> bool {
+        let moved = self.active.moved(dx, dy);
+        if self.board.collides(&moved) {
+            return false;
+        }
+        self.active = moved;
+        self.last_rotation = None;
+        self.on_active_changed();
+        true
+    }
+
+    fn try_rotate(&mut self, direction: RotationDirection) -> bool {
+        match self.active.rotation_tests(direction).into_iter().enumerate().find(|(_, piece)| !self.board.collides(piece)) {
+            Some((kick, rotated)) => {
+                self.active = rotated;
+                self.last_rotation = Some((direction, kick));
+                self.on_active_changed();
+                true
+            },
+            None => false,
+        }
+    }
+
+    fn lock(&mut self) {
+        let t_spin = self.last_rotation.and_then(|(direction, kick)| t_spin::detect(&self.board, &self.active, direction, kick));
+        self.board.lock(&self.active);
+        self.pieces_placed += 1;
+        let lines = self.board.clear_full_rows() as u64;
+        self.lines_cleared += lines;
+        self.score += lines * LINE_SCORE;
+        if let Some(t_spin) = t_spin {
+            self.score += t_spin::bonus(t_spin, lines);
+            self.last_t_spin = Some(TSpinClear { t_spin, lines });
+        }
+
+        // Garbage only arrives if nothing was cleared
+        if lines == 0 && self.pending_garbage > 0 {
+            let hole = self.randomizer.random_between(0, self.board.width() as i32 - 1) as usize;
+            if !self.board.add_garbage_rows(self.pending_garbage as usize, hole) {
+                self.game_over = true;
+            }
+            self.pending_garbage = 0;
+        }
+
+        self.queue.push_back(PieceKind::from_index(self.randomizer.random_between(0, 6)));
+        let next = self.queue.pop_front().unwrap();
+        self.active = Self::spawn_piece(next, self.board.width());
+        self.lock_time = None;
+        self.lock_starts = 0;
+        self.lowest_y = self.active.y;
+        self.last_rotation = None;
+        if self.board.collides(&self.active) {
+            self.game_over = true;
+        }else {
+            self.on_active_changed();
+        }
+    }
+
+    /// Garbage rows to add the next time a piece
+    /// locks without clearing a line.
+    pub fn queue_garbage(&mut self, lines: u32) {
+        self.pending_garbage += lines;
+    }
+
+    /// Removes up to the given amount of pending garbage.
+    /// Returns how much was removed.
+    pub fn cancel_garbage(&mut self, lines: u32) -> u32 {
+        let canceled = lines.min(self.pending_garbage);
+        self.pending_garbage -= canceled;
+        canceled
+    }
+
+    pub fn pending_garbage(&self) -> u32 {
+        self.pending_garbage
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,151 @@
+use super::srs;
+
+/// The seven tetrominos.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum PieceKind { I, J, L, O, S, T, Z }
+
+impl PieceKind {
+    /// Same order as tetris_core used, so randomizers stay compatible.
+    pub fn from_index(index: i32) -> Self {
+        match index {
+            0 => PieceKind::I,
+            1 => PieceKind::J,
+            2 => PieceKind::L,
+            3 => PieceKind::O,
+            4 => PieceKind::S,
+            5 => PieceKind::T,
+            _ => PieceKind::Z,
+        }
+    }
+
+    pub fn index(&self) -> i32 {
+        match self {
+            PieceKind::I => 0,
+            PieceKind::J => 1,
+            PieceKind::L => 2,
+            PieceKind::O => 3,
+            PieceKind::S => 4,
+            PieceKind::T => 5,
+            PieceKind::Z => 6,
+        }
+    }
+
+    /// Size of the square box the piece rotates in.
+    fn box_size(&self) -> i32 {
+        match self {
+            PieceKind::I | PieceKind::O => 4,
+            _ => 3,
+        }
+    }
+
+    /// Cells in spawn orientation (y pointing down) inside its box.
+    fn spawn_cells(&self) -> [(i32, i32); 4] {
+        match self {
+            PieceKind::I => [(0, 1), (1, 1), (2, 1), (3, 1)],
+            PieceKind::J => [(0, 0), (0, 1), (1, 1), (2, 1)],
+            PieceKind::L => [(2, 0), (0, 1), (1, 1), (2, 1)],
+            PieceKind::O => [(1, 0), (2, 0), (1, 1), (2, 1)],
+            PieceKind::S => [(1, 0), (2, 0), (0, 1), (1, 1)],
+            PieceKind::T => [(1, 0), (0, 1), (1, 1), (2, 1)],
+            PieceKind::Z => [(0, 0), (1, 0), (1, 1), (2, 1)],
+        }
+    }
+}
+
+/// Orientation of a piece as named by SRS.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Rotation {
+    /// Spawn orientation
+    Zero,
+    /// Rotated clockwise
+    Right,
+    /// Rotated twice
+    Two,
+    /// Rotated counter-clockwise
+    Left,
+}
+
+impl Rotation {
+    pub fn index(&self) -> usize {
+        match self {
+            Rotation::Zero => 0,
+            Rotation::Right => 1,
+            Rotation::Two => 2,
+            Rotation::Left => 3,
+        }
+    }
+
+    fn from_index(index: usize) -> Self {
+        match index % 4 {
+            0 => Rotation::Zero,
+            1 => Rotation::Right,
+            2 => Rotation::Two,
+            _ => Rotation::Left,
+        }
+    }
+
+    pub fn cw(&self) -> Self {
+        Self::from_index(self.index() + 1)
+    }
+
+    pub fn ccw(&self) -> Self {
+        Self::from_index(self.index() + 3)
+    }
+
+    pub fn flipped(&self) -> Self {
+        Self::from_index(self.index() + 2)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RotationDirection { Cw, Ccw, Half }
+
+/// A piece somewhere on (or above) the board.
+/// The position is the top left corner of its rotation box.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Piece {
+    pub kind: PieceKind,
+    pub x: i32,
+    pub y: i32,
+    pub rotation: Rotation,
+}
+
+impl Piece {
+    pub fn new(kind: PieceKind, x: i32, y: i32) -> Self {
+        Self { kind, x, y, rotation: Rotation::Zero }
+    }
+
+    /// Absolute board positions of all four cells.
+    pub fn cells(&self) -> [(i32, i32); 4] {
+        let size = self.kind.box_size();
+        let mut cells = self.kind.spawn_cells();
+        if self.kind != PieceKind::O {
+            for _ in 0..self.rotation.index() {
+                // Rotate clockwise inside the box
+                for cell in cells.iter_mut() {
+                    *cell = (size - 1 - cell.1, cell.0);
+                }
+            }
+        }
+        cells.map(|(x, y)| (self.x + x, self.y + y))
+    }
+
+    pub fn moved(&self, dx: i32, dy: i32) -> Self {
+        Self { x: self.x + dx, y: self.y + dy, ..*self }
+    }
+
+    /// All positions to try for a rotation in the order
+    /// they should be tested (see srs.rs).
+    pub fn rotation_tests(&self, direction: RotationDirection) -> Vec<Piece> {
+        let rotation = match direction {
+            RotationDirection::Cw => self.rotation.cw(),
+            RotationDirection::Ccw => self.rotation.ccw(),
+            RotationDirection::Half => self.rotation.flipped(),
+        };
+        let rotated = Self { rotation, ..*self };
+        srs::kicks(self.kind, self.rotation, direction)
+            .iter()
+            .map(|(dx, dy)| rotated.moved(*dx, *dy))
+            .collect()
+    }
+}
@@ -0,0 +1,55 @@
+//! Wall kicks of the Super Rotation System.
+//!
+//! See https://tetris.wiki/Super_Rotation_System . The tables there
+//! have y pointing up, the ones here have it pointing down like the board.
+
+use super::piece::{PieceKind, Rotation, RotationDirection};
+
+type Kicks = [(i32, i32); 5];
+
+/// J, L, S, T and Z. Indexed by the rotation before turning clockwise.
+const JLSTZ_CW: [Kicks; 4] = [
+    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)], // 0 -> R
+    [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],   // R -> 2
+    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],    // 2 -> L
+    [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)], // L -> 0
+];
+
+/// J, L, S, T and Z. Indexed by the rotation before turning counter-clockwise.
+const JLSTZ_CCW: [Kicks; 4] = [
+    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],    // 0 -> L
+    [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],   // R -> 0
+    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)], // 2 -> R
+    [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)], // L -> 2
+];
+
+const I_CW: [Kicks; 4] = [
+    [(0, 0), (-2, 0), (1, 0), (-2, 1), (1, -2)],  // 0 -> R
+    [(0, 0), (-1, 0), (2, 0), (-1, -2), (2, 1)],  // R -> 2
+    [(0, 0), (2, 0), (-1, 0), (2, -1), (-1, 2)],  // 2 -> L
+    [(0, 0), (1, 0), (-2, 0), (1, 2), (-2, -1)],  // L -> 0
+];
+
+const I_CCW: [Kicks; 4] = [
+    [(0, 0), (-1, 0), (2, 0), (-1, -2), (2, 1)],  // 0 -> L
+    [(0, 0), (2, 0), (-1, 0), (2, -1), (-1, 2)],  // R -> 0
+    [(0, 0), (1, 0), (-2, 0), (1, 2), (-2, -1)],  // 2 -> R
+    [(0, 0), (-2, 0), (1, 0), (-2, 1), (1, -2)],  // L -> 2
+];
+
+/// 180° rotations aren't part of SRS. These are the
+/// simple kicks many modern games use for every piece.
+const HALF: [(i32, i32); 6] = [(0, 0), (0, -1), (1, -1), (-1, -1), (1, 0), (-1, 0)];
+
+/// Offsets to test in order when rotating from `from`.
+pub fn kicks(kind: PieceKind, from: Rotation, direction: RotationDirection) -> &'static [(i32, i32)] {
+    let index = from.index();
+    match (kind, direction) {
+        (PieceKind::O, _) => &[(0, 0)],
+        (_, RotationDirection::Half) => &HALF,
+        (PieceKind::I, RotationDirection::Cw) => &I_CW[index],
+        (PieceKind::I, RotationDirection::Ccw) => &I_CCW[index],
+        (_, RotationDirection::Cw) => &JLSTZ_CW[index],
+        (_, RotationDirection::Ccw) => &JLSTZ_CCW[index],
+    }
+}
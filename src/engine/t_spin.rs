@@ -0,0 +1,54 @@
+//! T-spin recognition using the 3-corner rule.
+//!
+//! A T piece that was rotated as its last move counts as T-spin if
+//! at least three of the four cells diagonal to its center are
+//! filled. If only one of the two corners it points to is filled,
+//! it is a mini T-spin, unless the rotation needed the last kick.
+
+use super::board::Board;
+use super::piece::{Piece, PieceKind, Rotation, RotationDirection};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TSpin { Mini, Full }
+
+/// Index of the SRS kick that turns a mini into a full
+/// T-spin (the one moving the piece two rows).
+const UPGRADING_KICK: usize = 4;
+
+/// Checks the piece right before it locks. `direction` and `kick` (index
+/// of the test that succeeded) are from its last rotation.
+pub fn detect(board: &Board, piece: &Piece, direction: RotationDirection, kick: usize) -> Option<TSpin> {
+    if piece.kind != PieceKind::T {
+        return None;
+    }
+    // Center of the T is always in the middle of its box
+    let filled = |dx: i32, dy: i32| board.is_filled(piece.x + 1 + dx, piece.y + 1 + dy);
+    let (front, back) = match piece.rotation {
+        Rotation::Zero => ([(-1, -1), (1, -1)], [(-1, 1), (1, 1)]),
+        Rotation::Right => ([(1, -1), (1, 1)], [(-1, -1), (-1, 1)]),
+        Rotation::Two => ([(-1, 1), (1, 1)], [(-1, -1), (1, -1)]),
+        Rotation::Left => ([(-1, -1), (-1, 1)], [(1, -1), (1, 1)]),
+    };
+    let front_count = front.iter().filter(|(dx, dy)| filled(*dx, *dy)).count();
+    let back_count = back.iter().filter(|(dx, dy)| filled(*dx, *dy)).count();
+
+    if front_count + back_count < 3 {
+        None
+    }else if front_count == 2 || (direction != RotationDirection::Half && kick == UPGRADING_KICK) {
+        Some(TSpin::Full)
+    }else {
+        Some(TSpin::Mini)
+    }
+}
+
+/// Points on top of the ones for the cleared lines.
+pub fn bonus(t_spin: TSpin, lines: u64) -> u64 {
+    match (t_spin, lines) {
+        (TSpin::Mini, 0) => 100,
+        (TSpin::Mini, _) => 200,
+        (TSpin::Full, 0) => 400,
+        (TSpin::Full, 1) => 800,
+        (TSpin::Full, 2) => 1200,
+        (TSpin::Full, _) => 1600,
+    }
+}
@@ -0,0 +1,470 @@
+use super::*;
+use super::piece::Rotation;
+
+/// Hands out the given numbers in a loop.
+struct Sequence {
+    numbers: Vec<i32>,
+    next: std::cell::Cell<usize>,
+}
+
+impl Sequence {
+    fn new(numbers: &[i32]) -> Box<Self> {
+        Box::new(Self { numbers: numbers.to_vec(), next: std::cell::Cell::new(0) })
+    }
+}
+
+impl Randomizer for Sequence {
+    fn random_between(&self, first: i32, last: i32) -> i32 {
+        let index = self.next.get();
+        self.next.set(index + 1);
+        self.numbers[index % self.numbers.len()].max(first).min(last)
+    }
+}
+
+const I: i32 = 0;
+const T: i32 = 5;
+
+/// '#' is a filled cell, everything else an empty one.
+fn board(rows: &[&str]) -> Board {
+    let mut board = Board::new(rows[0].len(), rows.len());
+    for (y, row) in rows.iter().enumerate() {
+        for (x, c) in row.chars().enumerate() {
+            if c == '#' {
+                board.set(x, y, Some(Cell::Garbage));
+            }
+        }
+    }
+    board
+}
+
+/// A 10x22 board with the given rows at the bottom.
+fn board_with_bottom(rows: &[&str]) -> Board {
+    let mut all = vec![".........."; 22 - rows.len()];
+    all.extend_from_slice(rows);
+    board(&all)
+}
+
+fn new_game(board: Board, pieces: &[i32]) -> Game {
+    new_game_with(board, pieces, 1.0, LockDelayConfig::GUIDELINE)
+}
+
+fn new_game_with(board: Board, pieces: &[i32], speed: f64, lock_delay: LockDelayConfig) -> Game {
+    let size = Size { width: board.width(), height: board.height() };
+    let mut game = Game::new(&size, speed, lock_delay, Sequence::new(pieces));
+    game.set_board(board);
+    game
+}
+
+fn piece(kind: PieceKind, x: i32, y: i32, rotation: Rotation) -> Piece {
+    Piece { kind, x, y, rotation }
+}
+
+/// Start and end rotation with the kicks between them.
+type Transition = (Rotation, Rotation, [(i32, i32); 5]);
+
+/// Kick tables from the SRS wiki page, with y pointing up like there.
+const WIKI_JLSTZ: [Transition; 8] = [
+    (Rotation::Zero, Rotation::Right, [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)]),
+    (Rotation::Right, Rotation::Zero, [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)]),
+    (Rotation::Right, Rotation::Two, [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)]),
+    (Rotation::Two, Rotation::Right, [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)]),
+    (Rotation::Two, Rotation::Left, [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)]),
+    (Rotation::Left, Rotation::Two, [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)]),
+    (Rotation::Left, Rotation::Zero, [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)]),
+    (Rotation::Zero, Rotation::Left, [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)]),
+];
+
+const WIKI_I: [Transition; 8] = [
+    (Rotation::Zero, Rotation::Right, [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)]),
+    (Rotation::Right, Rotation::Zero, [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)]),
+    (Rotation::Right, Rotation::Two, [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)]),
+    (Rotation::Two, Rotation::Right, [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)]),
+    (Rotation::Two, Rotation::Left, [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)]),
+    (Rotation::Left, Rotation::Two, [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)]),
+    (Rotation::Left, Rotation::Zero, [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)]),
+    (Rotation::Zero, Rotation::Left, [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)]),
+];
+
+#[test]
+fn kicks_match_the_srs_tables() {
+    let tables = [
+        (&[PieceKind::J, PieceKind::L, PieceKind::S, PieceKind::T, PieceKind::Z][..], &WIKI_JLSTZ),
+        (&[PieceKind::I][..], &WIKI_I),
+    ];
+    for (kinds, table) in tables.iter() {
+        for kind in kinds.iter() {
+            for (from, to, wiki) in table.iter() {
+                let direction = if *to == from.cw() { RotationDirection::Cw } else { RotationDirection::Ccw };
+                let expected: Vec<(i32, i32)> = wiki.iter().map(|(x, y)| (*x, -*y)).collect();
+                assert_eq!(srs::kicks(*kind, *from, direction), &expected[..], "{:?} {:?} -> {:?}", kind, from, to);
+
+                let tests = piece(*kind, 3, 5, *from).rotation_tests(direction);
+                assert!(tests.iter().all(|test| test.rotation == *to));
+                let offsets: Vec<(i32, i32)> = tests.iter().map(|test| (test.x - 3, test.y - 5)).collect();
+                assert_eq!(offsets, expected);
+            }
+        }
+    }
+}
+
+#[test]
+fn half_rotations_and_o_pieces() {
+    let half = [(0, 0), (0, -1), (1, -1), (-1, -1), (1, 0), (-1, 0)];
+    for index in 0..7 {
+        let kind = PieceKind::from_index(index);
+        for rotation in [Rotation::Zero, Rotation::Right, Rotation::Two, Rotation::Left].iter() {
+            let tests = piece(kind, 3, 5, *rotation).rotation_tests(RotationDirection::Half);
+            if kind == PieceKind::O {
+                assert_eq!(tests, vec![piece(kind, 3, 5, rotation.flipped())]);
+                assert_eq!(srs::kicks(kind, *rotation, RotationDirection::Cw), &[(0, 0)]);
+                assert_eq!(srs::kicks(kind, *rotation, RotationDirection::Ccw), &[(0, 0)]);
+            }else {
+                assert_eq!(srs::kicks(kind, *rotation, RotationDirection::Half), &half[..]);
+                assert!(tests.iter().all(|test| test.rotation == rotation.flipped()));
+            }
+        }
+    }
+}
+
+#[test]
+fn rotations_turn_clockwise_inside_the_box() {
+    let cases = [
+        (Rotation::Zero, [(1, 0), (0, 1), (1, 1), (2, 1)]),
+        (Rotation::Right, [(1, 0), (1, 1), (2, 1), (1, 2)]),
+        (Rotation::Two, [(0, 1), (1, 1), (2, 1), (1, 2)]),
+        (Rotation::Left, [(1, 0), (0, 1), (1, 1), (1, 2)]),
+    ];
+    for (rotation, expected) in cases.iter() {
+        let mut cells = piece(PieceKind::T, 0, 0, *rotation).cells();
+        cells.sort_by_key(|(x, y)| (*y, *x));
+        assert_eq!(&cells, expected, "{:?}", rotation);
+    }
+}
+
+#[test]
+fn wall_and_floor_kicks() {
+    use RotationDirection::*;
+    // Start, direction, end and index of the used kick on an empty board
+    let cases = [
+        // I against the left wall
+        (piece(PieceKind::I, -1, 5, Rotation::Left), Cw, piece(PieceKind::I, 0, 5, Rotation::Zero), 1),
+        // I against the right wall
+        (piece(PieceKind::I, 7, 5, Rotation::Right), Ccw, piece(PieceKind::I, 6, 5, Rotation::Zero), 2),
+        // T against the right wall
+        (piece(PieceKind::T, 8, 5, Rotation::Left), Cw, piece(PieceKind::T, 7, 5, Rotation::Zero), 1),
+        // T against the left wall
+        (piece(PieceKind::T, -1, 5, Rotation::Right), Ccw, piece(PieceKind::T, 0, 5, Rotation::Zero), 1),
+        // I lying on the floor
+        (piece(PieceKind::I, 3, 20, Rotation::Zero), Cw, piece(PieceKind::I, 4, 18, Rotation::Right), 4),
+        // T lying on the floor
+        (piece(PieceKind::T, 3, 20, Rotation::Zero), Cw, piece(PieceKind::T, 2, 19, Rotation::Right), 2),
+        (piece(PieceKind::T, 3, 20, Rotation::Zero), Half, piece(PieceKind::T, 3, 19, Rotation::Two), 1),
+        // Free space doesn't kick
+        (piece(PieceKind::T, 3, 5, Rotation::Zero), Half, piece(PieceKind::T, 3, 5, Rotation::Two), 0),
+    ];
+    for (start, direction, end, kick) in cases.iter() {
+        let mut game = new_game(Board::new(10, 22), &[T]);
+        assert!(!game.board.collides(start));
+        game.active = *start;
+        let action = match direction {
+            Cw => Action::RotateCw,
+            Ccw => Action::RotateCcw,
+            Half => Action::Rotate180,
+        };
+        assert!(game.perform(action), "{:?} {:?}", start, direction);
+        assert_eq!(game.active, *end, "{:?} {:?}", start, direction);
+        assert_eq!(game.last_rotation, Some((*direction, *kick)));
+    }
+}
+
+#[test]
+fn rotations_fail_without_space() {
+    let mut game = new_game(board_with_bottom(&[
+        "##########",
+        "####.#####",
+        "###...####",
+    ]), &[T]);
+    let slot = piece(PieceKind::T, 3, 20, Rotation::Zero);
+    assert!(!game.board.collides(&slot));
+    game.active = slot;
+    for action in [Action::RotateCw, Action::RotateCcw, Action::Rotate180].iter() {
+        assert!(!game.perform(*action), "{:?}", action);
+        assert_eq!(game.active, slot);
+    }
+}
+
+#[test]
+fn full_rows_are_cleared() {
+    let cases: [(&[&str], usize, &[&str]); 4] = [
+        (&["#.", ".#"], 0, &["#.", ".#"]),
+        (&["#.", "##", ".#"], 1, &["..", "#.", ".#"]),
+        (&["#.", "##", ".#", "##"], 2, &["..", "..", "#.", ".#"]),
+        (&["##", "##", "##"], 3, &["..", "..", ".."]),
+    ];
+    for (before, lines, after) in cases.iter() {
+        let mut cleared = board(before);
+        assert_eq!(cleared.clear_full_rows(), *lines, "{:?}", before);
+        assert_eq!(cleared, board(after), "{:?}", before);
+    }
+}
+
+#[test]
+fn hard_drops_lock_and_clear_lines() {
+    let mut game = new_game(board_with_bottom(&[
+        "###....###",
+    ]), &[I, T]);
+    assert_eq!(game.active.kind, PieceKind::I);
+    assert!(game.perform(Action::HardDrop));
+    assert_eq!(game.pieces_placed(), 1);
+    assert_eq!(game.lines_cleared(), 1);
+    assert_eq!(game.get_score(), LINE_SCORE);
+    assert!(game.board().is_empty());
+    // The next piece from the queue spawned
+    assert_eq!(game.active, Piece::new(PieceKind::T, 3, 0));
+
+    let mut game = new_game(board_with_bottom(&[
+        "#####.####",
+        "#####.####",
+        "#####.####",
+        "#####.####",
+    ]), &[I]);
+    assert!(game.perform(Action::RotateCw));
+    assert!(game.perform(Action::HardDrop));
+    assert_eq!(game.lines_cleared(), 4);
+    assert_eq!(game.get_score(), 4 * LINE_SCORE);
+    assert!(game.board().is_empty());
+}
+
+#[test]
+fn pieces_lock_after_the_lock_delay() {
+    let mut game = new_game(Board::new(10, 22), &[T]);
+    game.update(0.1);
+    assert_eq!(game.active.y, 0);
+    game.update(0.11);
+    assert_eq!(game.active.y, 1);
+
+    while game.perform(Action::MoveDown) { }
+    assert_eq!(game.active.y, 20);
+    assert_eq!(game.next_update_in(), Some(0.5));
+    game.update(0.3);
+    assert_eq!(game.pieces_placed(), 0);
+    game.update(0.25);
+    assert_eq!(game.pieces_placed(), 1);
+    assert_eq!(game.board().get(4, 20), Some(Cell::Piece(PieceKind::T)));
+    assert_eq!(game.active, Piece::new(PieceKind::T, 3, 0));
+}
+
+#[test]
+fn speed_scales_gravity_but_not_the_lock_delay() {
+    let mut game = new_game_with(Board::new(10, 22), &[T], 4.0, LockDelayConfig::GUIDELINE);
+    assert_eq!(game.next_update_in(), Some(0.05));
+    game.update(0.06);
+    assert_eq!(game.active.y, 1);
+
+    while game.perform(Action::MoveDown) { }
+    assert_eq!(game.next_update_in(), Some(0.5));
+    game.update(0.3);
+    assert_eq!(game.pieces_placed(), 0);
+    game.update(0.25);
+    assert_eq!(game.pieces_placed(), 1);
+}
+
+#[test]
+fn lock_delay_restarts_at_most_max_resets_times() {
+    let lock_delay = LockDelayConfig { delay: Duration::from_millis(500), max_resets: 3 };
+    let mut game = new_game_with(Board::new(10, 22), &[T], 1.0, lock_delay);
+    while game.perform(Action::MoveDown) { }
+    for reset in 0..3 {
+        game.update(0.4);
+        let action = if reset % 2 == 0 { Action::MoveLeft } else { Action::MoveRight };
+        assert!(game.perform(action));
+        assert_eq!(game.next_update_in(), Some(0.5), "Reset {}", reset + 1);
+    }
+    // No resets left, the time keeps running
+    game.update(0.4);
+    assert!(game.perform(Action::RotateCw));
+    assert!(game.next_update_in().unwrap() < 0.11);
+    game.update(0.11);
+    assert_eq!(game.pieces_placed(), 1);
+
+    // Without resets, moving doesn't give any extra time
+    let lock_delay = LockDelayConfig { max_resets: 0, ..lock_delay };
+    let mut game = new_game_with(Board::new(10, 22), &[T], 1.0, lock_delay);
+    while game.perform(Action::MoveDown) { }
+    game.update(0.4);
+    assert!(game.perform(Action::MoveLeft));
+    game.update(0.11);
+    assert_eq!(game.pieces_placed(), 1);
+}
+
+#[test]
+fn reaching_a_new_lowest_row_gives_resets_back() {
+    let lock_delay = LockDelayConfig { delay: Duration::from_millis(500), max_resets: 1 };
+    let mut game = new_game_with(board_with_bottom(&[
+        "........##",
+        "........##",
+    ]), &[T], 1.0, lock_delay);
+    game.active = piece(PieceKind::T, 7, 0, Rotation::Zero);
+    while game.perform(Action::MoveDown) { }
+    assert_eq!(game.active.y, 18);
+    game.update(0.4);
+    assert!(game.perform(Action::MoveLeft));
+    assert_eq!(game.next_update_in(), Some(0.5));
+    game.update(0.4);
+    // Falls off the ledge
+    assert!(game.perform(Action::MoveLeft));
+    assert_eq!(game.lock_time, None);
+    while game.perform(Action::MoveDown) { }
+    assert_eq!(game.active.y, 20);
+    assert_eq!(game.next_update_in(), Some(0.5));
+    game.update(0.4);
+    assert!(game.perform(Action::MoveLeft));
+    assert_eq!(game.next_update_in(), Some(0.5));
+    game.update(0.4);
+    assert!(game.perform(Action::MoveLeft));
+    game.update(0.11);
+    assert_eq!(game.pieces_placed(), 1);
+}
+
+#[test]
+fn garbage_arrives_when_nothing_is_cleared() {
+    // Pieces for the queue, then the hole
+    let mut game = new_game(Board::new(10, 22), &[T, T, T, T, 2]);
+    game.queue_garbage(2);
+    assert!(game.perform(Action::HardDrop));
+    assert_eq!(game.pending_garbage(), 0);
+    assert!(!game.is_game_over());
+    let mut expected = board_with_bottom(&[
+        "##.#######",
+        "##.#######",
+    ]);
+    expected.lock(&piece(PieceKind::T, 3, 18, Rotation::Zero));
+    assert_eq!(game.board(), &expected);
+}
+
+#[test]
+fn clearing_lines_holds_back_garbage() {
+    let mut game = new_game(board_with_bottom(&[
+        "###....###",
+    ]), &[I]);
+    game.queue_garbage(3);
+    assert_eq!(game.cancel_garbage(1), 1);
+    assert!(game.perform(Action::HardDrop));
+    assert!(game.board().is_empty());
+    assert_eq!(game.pending_garbage(), 2);
+    assert_eq!(game.cancel_garbage(5), 2);
+    assert_eq!(game.pending_garbage(), 0);
+}
+
+#[test]
+fn garbage_pushing_out_blocks_tops_out() {
+    let mut pushed = board(&["....", "#...", "##.#"]);
+    assert!(pushed.add_garbage_rows(1, 3));
+    assert_eq!(pushed, board(&["#...", "##.#", "###."]));
+    assert!(!pushed.add_garbage_rows(1, 0));
+    assert_eq!(pushed, board(&["##.#", "###.", ".###"]));
+
+    let mut game = new_game(board_with_bottom(&[
+        "#.........",
+    ]), &[T, T, T, T, 0]);
+    game.queue_garbage(21);
+    assert!(game.perform(Action::HardDrop));
+    assert!(game.is_game_over());
+}
+
+#[test]
+fn blocked_spawns_top_out() {
+    let mut rows = vec!["#########."; 20];
+    rows.insert(0, "..........");
+    rows.insert(0, "..........");
+    let mut game = new_game(board(&rows), &[T]);
+    assert!(!game.is_game_over());
+    assert!(game.perform(Action::HardDrop));
+    assert_eq!(game.pieces_placed(), 1);
+    assert!(game.is_game_over());
+    assert!(!game.perform(Action::MoveLeft));
+    assert_eq!(game.next_update_in(), None);
+
+    let game = new_game(board_with_bottom(&["...#......"; 22]), &[T]);
+    assert!(game.is_game_over());
+}
+
+#[test]
+fn t_spins_follow_the_three_corner_rule() {
+    use RotationDirection::*;
+    // The T sits in the top left corner of the board
+    let cases = [
+        (["#.#", "...", "#.#"], PieceKind::T, Rotation::Zero, Cw, 0, Some(TSpin::Full)),
+        (["#.#", "...", "#.."], PieceKind::T, Rotation::Zero, Cw, 0, Some(TSpin::Full)),
+        // Only one corner it points to
+        (["#..", "...", "#.#"], PieceKind::T, Rotation::Zero, Cw, 0, Some(TSpin::Mini)),
+        (["#..", "...", "#.#"], PieceKind::T, Rotation::Zero, Ccw, 3, Some(TSpin::Mini)),
+        // Unless the rotation needed the last kick
+        (["#..", "...", "#.#"], PieceKind::T, Rotation::Zero, Cw, 4, Some(TSpin::Full)),
+        (["#..", "...", "#.#"], PieceKind::T, Rotation::Zero, Half, 4, Some(TSpin::Mini)),
+        // Two corners aren't enough
+        (["...", "...", "#.#"], PieceKind::T, Rotation::Two, Ccw, 0, None),
+        (["#.#", "...", "#.#"], PieceKind::T, Rotation::Two, Half, 0, Some(TSpin::Full)),
+        (["#..", "...", "..#"], PieceKind::T, Rotation::Zero, Cw, 4, None),
+        // Only T pieces spin
+        (["..#", "...", "#.#"], PieceKind::J, Rotation::Zero, Cw, 0, None),
+        (["#.#", "...", "#.."], PieceKind::T, Rotation::Right, Cw, 0, Some(TSpin::Mini)),
+    ];
+    for (rows, kind, rotation, direction, kick, expected) in cases.iter() {
+        let piece = piece(*kind, 0, 0, *rotation);
+        let board = board(rows);
+        assert!(!board.collides(&piece), "{:?} {:?}", rows, rotation);
+        assert_eq!(t_spin::detect(&board, &piece, *direction, *kick), *expected, "{:?} {:?} {:?}", rows, rotation, kick);
+    }
+
+    // Walls count as filled corners
+    let board = board(&[".#", "..", ".."]);
+    let piece = piece(PieceKind::T, -1, 0, Rotation::Right);
+    assert!(!board.collides(&piece));
+    assert_eq!(t_spin::detect(&board, &piece, RotationDirection::Cw, 0), Some(TSpin::Mini));
+}
+
+/// Slot for a T-spin double with the T right above it.
+fn t_spin_double_game() -> Game {
+    let mut game = new_game(board_with_bottom(&[
+        "####......",
+        "###...####",
+        "####.#####",
+    ]), &[T]);
+    game.active = piece(PieceKind::T, 3, 19, Rotation::Right);
+    assert!(!game.board.collides(&game.active));
+    game
+}
+
+#[test]
+fn t_spins_need_a_rotation_as_last_move() {
+    let mut game = t_spin_double_game();
+    assert!(game.perform(Action::RotateCw));
+    assert_eq!(game.active, piece(PieceKind::T, 3, 19, Rotation::Two));
+    assert!(game.perform(Action::HardDrop));
+    assert_eq!(game.lines_cleared(), 2);
+    assert_eq!(game.take_t_spin(), Some(TSpinClear { t_spin: TSpin::Full, lines: 2 }));
+    assert_eq!(game.take_t_spin(), None);
+    assert_eq!(game.get_score(), 2 * LINE_SCORE + t_spin::bonus(TSpin::Full, 2));
+
+    // The same spot without rotating into it
+    let mut game = t_spin_double_game();
+    game.active = piece(PieceKind::T, 3, 19, Rotation::Two);
+    assert!(game.perform(Action::HardDrop));
+    assert_eq!(game.lines_cleared(), 2);
+    assert_eq!(game.take_t_spin(), None);
+    assert_eq!(game.get_score(), 2 * LINE_SCORE);
+
+    // Moving after the rotation, even into three corners
+    let mut game = new_game(board_with_bottom(&[
+        "#.........",
+        "#.........",
+        "#.#.......",
+    ]), &[T]);
+    game.active = piece(PieceKind::T, 0, 5, Rotation::Zero);
+    assert!(game.perform(Action::RotateCw));
+    assert!(game.perform(Action::HardDrop));
+    assert_eq!(game.pieces_placed(), 1);
+    assert_eq!(game.board().get(1, 19), Some(Cell::Piece(PieceKind::T)));
+    assert_eq!(game.take_t_spin(), None);
+}
@@ -0,0 +1,109 @@
+//! Press-and-hold recognition with auto repeat.
+//!
+//! A hold starts immediately when pressed. After the delayed
+//! auto shift (DAS) it repeats with the auto repeat rate (ARR)
+//! until released.
+
+use libremarkable::input::PhysicalButton;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct HoldConfig {
+    /// Delay after the first press until the repeating starts.
+    pub das: Duration,
+    /// Delay between repeats.
+    pub arr: Duration,
+}
+
+impl Default for HoldConfig {
+    fn default() -> Self {
+        // Slower than usual on purpose. The display needs some
+        // time to show every step anyway.
+        Self {
+            das: Duration::from_millis(250),
+            arr: Duration::from_millis(100),
+        }
+    }
+}
+
+/// What is being held down.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum HoldSource {
+    Finger(i32 /* Tracking id */),
+    Button(PhysicalButton),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum HoldEvent<V> {
+    Start(V),
+    Repeat(V),
+    End(V),
+}
+
+struct Hold<V> {
+    source: HoldSource,
+    value: V,
+    next_repeat: Instant,
+}
+
+/// Keeps track of everything that is currently held.
+/// Every hold carries a value (e.g. the action to repeat)
+/// which is given back with every event.
+pub struct HoldTracker<V: Copy> {
+    config: HoldConfig,
+    holds: Vec<Hold<V>>,
+}
+
+impl<V: Copy> HoldTracker<V> {
+    pub fn new(config: HoldConfig) -> Self {
+        Self { config, holds: vec![] }
+    }
+
+    pub fn press(&mut self, source: HoldSource, value: V, now: Instant) -> HoldEvent<V> {
+        // A source can only hold one thing at a time
+        self.holds.retain(|hold| hold.source != source);
+        self.holds.push(Hold { source, value, next_repeat: now + self.config.das });
+        HoldEvent::Start(value)
+    }
+
+    pub fn release(&mut self, source: HoldSource) -> Option<HoldEvent<V>> {
+        let index = self.holds.iter().position(|hold| hold.source == source)?;
+        Some(HoldEvent::End(self.holds.remove(index).value))
+    }
+
+    pub fn is_holding(&self, source: HoldSource) -> bool {
+        self.holds.iter().any(|hold| hold.source == source)
+    }
+
+    /// Returns the holds that are due to repeat. At most one repeat
+    /// per hold, so a long frame doesn't move a block across the
+    /// board at once. Missed repeats are skipped.
+    pub fn poll(&mut self, now: Instant) -> Vec<HoldEvent<V>> {
+        let arr = self.config.arr.max(Duration::from_millis(1));
+        let mut events = vec![];
+        for hold in self.holds.iter_mut() {
+            if hold.next_repeat <= now {
+                events.push(HoldEvent::Repeat(hold.value));
+                hold.next_repeat += arr;
+                if hold.next_repeat <= now {
+                    // Fell behind
+                    hold.next_repeat = now + arr;
+                }
+            }
+        }
+        events
+    }
+
+    /// When the next repeat becomes due. None if nothing is held.
+    pub fn next_repeat(&self) -> Option<Instant> {
+        self.holds.iter().map(|hold| hold.next_repeat).min()
+    }
+
+    /// Forget everything held, e.g. when pausing.
+    pub fn clear(&mut self) {
+        self.holds.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests;
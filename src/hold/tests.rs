@@ -0,0 +1,77 @@
+use super::*;
+
+const FINGER: HoldSource = HoldSource::Finger(1);
+
+fn ms(millis: u64) -> Duration {
+    Duration::from_millis(millis)
+}
+
+fn tracker() -> HoldTracker<char> {
+    HoldTracker::new(HoldConfig { das: ms(250), arr: ms(100) })
+}
+
+#[test]
+fn repeats_start_after_the_initial_delay() {
+    let start = Instant::now();
+    let mut tracker = tracker();
+    assert_eq!(tracker.press(FINGER, 'l', start), HoldEvent::Start('l'));
+    assert_eq!(tracker.next_repeat(), Some(start + ms(250)));
+    assert!(tracker.poll(start + ms(249)).is_empty());
+    assert_eq!(tracker.poll(start + ms(250)), vec![HoldEvent::Repeat('l')]);
+}
+
+#[test]
+fn repeats_with_the_repeat_rate() {
+    let start = Instant::now();
+    let mut tracker = tracker();
+    tracker.press(FINGER, 'l', start);
+    let repeats: usize = (0..=550).step_by(10)
+        .map(|millis| tracker.poll(start + ms(millis)).len())
+        .sum();
+    // At 250, 350, 450 and 550
+    assert_eq!(repeats, 4);
+    assert_eq!(tracker.next_repeat(), Some(start + ms(650)));
+}
+
+#[test]
+fn stalls_give_a_single_repeat() {
+    let start = Instant::now();
+    let mut tracker = tracker();
+    tracker.press(FINGER, 'l', start);
+    assert_eq!(tracker.poll(start + ms(2000)), vec![HoldEvent::Repeat('l')]);
+    assert_eq!(tracker.next_repeat(), Some(start + ms(2100)));
+    assert!(tracker.poll(start + ms(2050)).is_empty());
+}
+
+#[test]
+fn release_ends_the_hold() {
+    let start = Instant::now();
+    let mut tracker = tracker();
+    tracker.press(FINGER, 'l', start);
+    tracker.press(HoldSource::Finger(2), 'r', start);
+    assert_eq!(tracker.release(FINGER), Some(HoldEvent::End('l')));
+    assert_eq!(tracker.release(FINGER), None);
+    assert!(!tracker.is_holding(FINGER));
+    assert_eq!(tracker.poll(start + ms(300)), vec![HoldEvent::Repeat('r')]);
+}
+
+#[test]
+fn pressing_again_replaces_the_hold() {
+    let start = Instant::now();
+    let mut tracker = tracker();
+    tracker.press(FINGER, 'l', start);
+    tracker.press(FINGER, 'r', start + ms(200));
+    assert!(tracker.poll(start + ms(300)).is_empty());
+    assert_eq!(tracker.poll(start + ms(450)), vec![HoldEvent::Repeat('r')]);
+}
+
+#[test]
+fn clear_forgets_everything() {
+    let start = Instant::now();
+    let mut tracker = tracker();
+    tracker.press(FINGER, 'l', start);
+    tracker.press(HoldSource::Button(PhysicalButton::LEFT), 'l', start);
+    tracker.clear();
+    assert_eq!(tracker.next_repeat(), None);
+    assert!(tracker.poll(start + ms(1000)).is_empty());
+}
@@ -2,18 +2,37 @@
 extern crate downcast_rs;
 
 mod canvas;
+mod clock;
+mod config;
+mod debug;
+mod engine;
+mod hold;
+mod puzzle;
 mod scene;
+mod screenshot;
+mod signals;
+mod shutdown;
+mod status_bar;
 mod swipe;
+mod tap_zones;
+mod touch_trace;
+mod versus;
+mod widget;
 
 use clap::Parser;
 use crate::canvas::Canvas;
+use crate::debug::{DebugOverlay, FrameMetrics};
+use crate::config::Config;
 use crate::scene::*;
-use libremarkable::input::{InputDevice, InputEvent, ev::EvDevContext};
+use crate::touch_trace::TraceRecorder;
+use libremarkable::input::{InputDevice, InputEvent, MultitouchEvent, ev::EvDevContext};
+use std::path::PathBuf;
 use std::process::Command;
 use std::sync::LazyLock;
-use std::time::{Instant, Duration};
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::Instant;
 use std::thread::sleep;
-use tetris_core::Size;
+use crate::engine::Size;
 
 #[derive(Parser)]
 #[clap(version, author, help_template = "{before-help}{name} {version} - by {author}\n\n{all-args}{after-help}")]
@@ -25,9 +44,42 @@ pub struct Opts {
     /// Don't display the left and right software arrow buttons.
     #[clap(long, short = 'A')]
     no_arrow_buttons: bool,
+
+    /// Override a setting of the config file (e.g. `--set ghost_piece=true`).
+    /// Can be given multiple times.
+    #[clap(long, short = 's', value_name = "KEY=VALUE")]
+    set: Vec<String>,
+
+    /// Port to wait on for another device when hosting a versus game.
+    #[clap(long, default_value_t = versus::DEFAULT_PORT)]
+    versus_port: u16,
+
+    /// Address (host[:port]) of another device hosting a versus game to join.
+    #[clap(long, short = 'J')]
+    versus_join: Option<String>,
+
+    /// Use another config file than ~/.config/retris/retris.conf.
+    #[clap(long, short = 'c')]
+    config: Option<PathBuf>,
+
+    /// Record all touch input into the given file (e.g. for reproducing bugs).
+    #[clap(long)]
+    record_touch: Option<PathBuf>,
+
+    /// Replay touch input previously recorded with --record-touch.
+    #[clap(long)]
+    replay_touch: Option<PathBuf>,
+
+    /// Show frame time, refreshes, input latency and queued input at the top left.
+    #[clap(long)]
+    debug: bool,
+
+    /// Log the same metrics as --debug for every frame into the given file.
+    #[clap(long)]
+    debug_log: Option<PathBuf>,
 }
 
-pub static CLI_OPTS: LazyLock<Opts> = LazyLock::new(|| Opts::parse());
+pub static CLI_OPTS: LazyLock<Opts> = LazyLock::new(Opts::parse);
 
 fn main() {
     let only_exit_to_xochitl = if ! CLI_OPTS.kill_xochitl {
@@ -40,57 +92,214 @@ fn main() {
         }else { false }
     } else { false };
 
+    shutdown::install(only_exit_to_xochitl);
     let mut canvas = Canvas::new();
+    canvas.set_theme(Config::current().theme);
 
     let (input_tx, input_rx) = std::sync::mpsc::channel::<InputEvent>();
+    signals::install(input_tx.clone());
     EvDevContext::new(InputDevice::GPIO, input_tx.clone()).start();
+    if let Some(ref replay_touch) = CLI_OPTS.replay_touch {
+        if let Err(err) = touch_trace::replay(replay_touch, input_tx.clone()) {
+            eprintln!("Failed to replay touch trace {:?}: {}", replay_touch, err);
+        }
+    }
     EvDevContext::new(InputDevice::Multitouch, input_tx).start();
     //EvDevContext::new(InputDevice::Wacom, input_tx.clone()).start();
-    const FPS: u16 = 30;
-    const FRAME_DURATION: Duration = Duration::from_millis(1000 / FPS as u64);
+
+    let mut touch_recorder = CLI_OPTS.record_touch.as_ref().and_then(|path| match TraceRecorder::create(path) {
+        Ok(recorder) => Some(recorder),
+        Err(err) => {
+            eprintln!("Failed to record touch input into {:?}: {}", path, err);
+            None
+        },
+    });
+
+    let mut debug_overlay = DebugOverlay::new(CLI_OPTS.debug, CLI_OPTS.debug_log.as_deref()).unwrap_or_else(|err| {
+        eprintln!("Failed to create debug log {:?}: {}", CLI_OPTS.debug_log, err);
+        DebugOverlay::new(CLI_OPTS.debug, None).unwrap()
+    });
+    let (input_rx, input_times) = if debug_overlay.is_enabled() {
+        let (input_rx, input_times) = debug::timestamp_input(input_rx);
+        (input_rx, Some(input_times))
+    }else {
+        (input_rx, None)
+    };
 
     let mut current_scene: Box<dyn Scene> = Box::new(MainMenuScene::new(None, only_exit_to_xochitl));
+    // Fingers that pressed on the status bar. Their whole touch is
+    // taken by the screenshot and not seen by the scene.
+    let mut screenshot_fingers: Vec<i32> = vec![];
 
     loop {
-        let before_input = Instant::now();
-        for event in input_rx.try_iter() {
-            current_scene.on_input(event);        
+        let resumed = if signals::take_suspend_request() {
+            current_scene.on_suspend();
+            signals::wait_for_resume();
+            true
+        }else if signals::take_resume_request() {
+            // Got stopped and continued without being asked first
+            current_scene.on_suspend();
+            true
+        }else { false };
+        if signals::take_exit_request() {
+            shutdown::exit(&mut canvas, 0);
+        }
+        if resumed {
+            // Drop input that was meant for other apps
+            for _ in input_rx.try_iter() {
+                if let Some(ref input_times) = input_times {
+                    input_times.pop();
+                }
+            }
+            screenshot_fingers.clear();
+            current_scene.invalidate();
+        }
+
+        // Sleep until there is input (or a signal) or the scene needs to be drawn again
+        let first_event = match current_scene.next_tick() {
+            Some(timeout) => match input_rx.recv_timeout(timeout) {
+                Ok(event) => Some(event),
+                Err(RecvTimeoutError::Timeout) => None,
+                Err(RecvTimeoutError::Disconnected) => {
+                    sleep(timeout);
+                    None
+                },
+            },
+            None => input_rx.recv().ok(),
+        };
+
+        let frame_start = Instant::now();
+        let mut first_input: Option<Instant> = None;
+        let mut queue_depth = 0;
+        let mut screenshot_requested = signals::take_screenshot_request();
+        for event in first_event.into_iter().chain(input_rx.try_iter()) {
+            let received = input_times.as_ref().and_then(|input_times| input_times.pop());
+            if event == (InputEvent::Unknown {}) {
+                // Only woke up the loop to check the signals
+                continue;
+            }
+            queue_depth += 1;
+            first_input = first_input.or(received);
+            if let (Some(ref mut recorder), InputEvent::MultitouchEvent { event }) = (&mut touch_recorder, &event) {
+                recorder.record(*event);
+            }
+            if let InputEvent::MultitouchEvent { event } = &event {
+                match event {
+                    MultitouchEvent::Press { finger } if status_bar::is_hitting(finger.pos) => {
+                        screenshot_requested = true;
+                        screenshot_fingers.push(finger.tracking_id);
+                        continue;
+                    },
+                    MultitouchEvent::Move { finger } if screenshot_fingers.contains(&finger.tracking_id) => continue,
+                    MultitouchEvent::Release { finger } if screenshot_fingers.contains(&finger.tracking_id) => {
+                        screenshot_fingers.retain(|id| *id != finger.tracking_id);
+                        continue;
+                    },
+                    _ => { },
+                }
+            }
+            current_scene.on_input(event);
         }
 
         current_scene.draw(&mut canvas);
+        if screenshot_requested {
+            match screenshot::take(&mut canvas, current_scene.as_ref(), &Config::current().screenshot_dir()) {
+                Ok(path) => println!("Saved screenshot to {:?}", path),
+                Err(err) => eprintln!("Failed to take screenshot: {}", err),
+            }
+        }
+        let refreshes = canvas.take_refresh_count();
+        if debug_overlay.is_enabled() {
+            debug_overlay.record(FrameMetrics {
+                frame_time: frame_start.elapsed(),
+                refreshes,
+                latency: first_input.filter(|_| refreshes > 0).map(|received| received.elapsed()),
+                queue_depth,
+            });
+            debug_overlay.draw(&mut canvas);
+        }
         current_scene = update(current_scene, &mut canvas, only_exit_to_xochitl);
 
-        // Wait remaining frame time
-        let elapsed = before_input.elapsed();
+        // Don't draw more often than needed when there is a lot of input
+        let elapsed = frame_start.elapsed();
         if elapsed < FRAME_DURATION {
             sleep(FRAME_DURATION - elapsed);
         }
     }
 }
 
-fn update(scene: Box<dyn Scene>, canvas: &mut Canvas, only_exit_to_xochitl: bool) -> Box<dyn Scene> {
+fn update(mut scene: Box<dyn Scene>, canvas: &mut Canvas, only_exit_to_xochitl: bool) -> Box<dyn Scene> {
+    if let Some(game_scene) = scene.downcast_mut::<GameScene>() {
+        if game_scene.settings_button_pressed {
+            // Come back to the paused game afterwards
+            game_scene.settings_button_pressed = false;
+            return Box::new(SettingsScene::new().with_previous(scene));
+        }
+    }
+
     if let Some(game_scene) = scene.downcast_ref::<GameScene>() {
-        if game_scene.is_game_over() {
-            return Box::new(MainMenuScene::new(Some(game_scene.get_score()), only_exit_to_xochitl));
+        if let Some(level) = game_scene.puzzle() {
+            if let Some(outcome) = game_scene.puzzle_outcome() {
+                return Box::new(PuzzleSelectScene::new(&game_scene.game_size()).with_result(level, outcome));
+            }else if game_scene.back_button_pressed {
+                return Box::new(PuzzleSelectScene::new(&game_scene.game_size()));
+            }
+        }else if game_scene.is_game_over() || game_scene.versus_outcome().is_some() {
+            return Box::new(
+                MainMenuScene::new(Some(game_scene.get_score()), only_exit_to_xochitl)
+                    .with_versus_outcome(game_scene.versus_outcome())
+            );
         }else if game_scene.back_button_pressed {
             return Box::new(MainMenuScene::new(None, only_exit_to_xochitl));
         }
     }else if let Some(main_menu_scene) = scene.downcast_ref::<MainMenuScene>() {
-        if main_menu_scene.play_easy_button_pressed {
-            return Box::new(GameScene::new(Size { width: 10, height: 22 }, 0.5));
-        }else if main_menu_scene.play_normal_button_pressed {
-            return Box::new(GameScene::new(Size { width: 10, height: 22 }, 1.0));
-        }else if main_menu_scene.play_hard_button_pressed {
-            return Box::new(GameScene::new(Size { width: 10, height: 22 }, 1.5));
-        }else if main_menu_scene.exit_xochitl_button_pressed {
-            canvas.clear();
-            canvas.update_full();
-            Command::new("systemctl").arg("start").arg("xochitl").status().ok();
-            std::process::exit(0);
-        }else if main_menu_scene.exit_button_pressed {
-            canvas.clear();
-            canvas.update_full();
-            std::process::exit(0);
+        match main_menu_scene.action {
+            Some(MainMenuAction::PlayEasy) => return Box::new(GameScene::new(Size { width: 10, height: 22 }, 0.5)),
+            Some(MainMenuAction::PlayNormal) => return Box::new(GameScene::new(Size { width: 10, height: 22 }, 1.0)),
+            Some(MainMenuAction::PlayHard) => return Box::new(GameScene::new(Size { width: 10, height: 22 }, 1.5)),
+            Some(MainMenuAction::Versus) => return Box::new(VersusLobbyScene::new()),
+            Some(MainMenuAction::Puzzles) => return Box::new(PuzzleSelectScene::new(&Size { width: 10, height: 22 })),
+            Some(MainMenuAction::Calibrate) => return Box::new(SwipeCalibrationScene::new()),
+            Some(MainMenuAction::Settings) => return Box::new(SettingsScene::new()),
+            // Also starts xochitl again if it was stopped
+            Some(MainMenuAction::ExitXochitl) | Some(MainMenuAction::Exit) => shutdown::exit(canvas, 0),
+            None => { },
+        }
+    }else if let Some(swipe_calibration_scene) = scene.downcast_ref::<SwipeCalibrationScene>() {
+        if swipe_calibration_scene.done {
+            return Box::new(MainMenuScene::new(None, only_exit_to_xochitl));
+        }
+    }else if let Some(settings_scene) = scene.downcast_mut::<SettingsScene>() {
+        if settings_scene.done {
+            canvas.set_theme(Config::current().theme);
+            return match settings_scene.take_previous() {
+                Some(mut previous) => {
+                    if let Some(game_scene) = previous.downcast_mut::<GameScene>() {
+                        game_scene.apply_config(&Config::current());
+                    }
+                    previous.invalidate();
+                    previous
+                },
+                None => Box::new(MainMenuScene::new(None, only_exit_to_xochitl)),
+            };
+        }
+    }else if let Some(puzzle_select_scene) = scene.downcast_mut::<PuzzleSelectScene>() {
+        if let Some(level) = puzzle_select_scene.selected_level.take() {
+            match GameScene::new_puzzle(Size { width: 10, height: 22 }, level) {
+                Ok(game_scene) => return Box::new(game_scene),
+                Err(err) => {
+                    eprintln!("{}", err);
+                    puzzle_select_scene.show_error(err);
+                },
+            }
+        }else if puzzle_select_scene.back_button_pressed {
+            return Box::new(MainMenuScene::new(None, only_exit_to_xochitl));
+        }
+    }else if let Some(versus_lobby_scene) = scene.downcast_mut::<VersusLobbyScene>() {
+        if let Some(connection) = versus_lobby_scene.connection.take() {
+            return Box::new(GameScene::new_versus(Size { width: 10, height: 22 }, 1.0, connection));
+        }else if versus_lobby_scene.back_button_pressed {
+            return Box::new(MainMenuScene::new(None, only_exit_to_xochitl));
         }
     }
     scene
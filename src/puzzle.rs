@@ -0,0 +1,274 @@
+//! Puzzle levels with a prepared board, a fixed sequence of pieces and a goal.
+//!
+//! Levels are text files. The board is aligned to the bottom of the
+//! playfield. Empty cells are `.`, cells of a piece are its letter and
+//! anything else is garbage. The board has to come last:
+//!
+//! ```text
+//! # Comment
+//! name: First T-Spin
+//! # lines <n>, perfect_clear, t_spin <n> or t_spin_double <n>
+//! goal: t_spin 1
+//! pieces: T I
+//! board:
+//! ..........
+//! ###...####
+//! ####.#####
+//! ```
+//!
+//! Besides the built-in levels, all `*.level` files in the `levels`
+//! directory next to the config file are loaded. Solved levels are
+//! remembered in `puzzles_solved` in the same directory.
+
+use crate::config::Config;
+use crate::engine::{Board, Cell, PieceKind, Size};
+use std::fmt::Display;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+const BUILTIN_LEVELS: [(&str, &str); 5] = [
+    ("01_first_lines", include_str!("../levels/01_first_lines.level")),
+    ("02_tetris", include_str!("../levels/02_tetris.level")),
+    ("03_t_spin", include_str!("../levels/03_t_spin.level")),
+    ("04_perfect_clear", include_str!("../levels/04_perfect_clear.level")),
+    ("05_t_spin_double", include_str!("../levels/05_t_spin_double.level")),
+];
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Goal {
+    /// Clear at least the given amount of lines.
+    Lines(u64),
+    /// Leave the board completely empty.
+    PerfectClear,
+    /// Clear lines with the given amount of (non-mini) T-spins.
+    TSpins(u64),
+    /// Clear at least two lines at once with the given
+    /// amount of (non-mini) T-spins.
+    TSpinDoubles(u64),
+}
+
+impl Display for Goal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Goal::Lines(1) => write!(f, "Clear a line"),
+            Goal::Lines(lines) => write!(f, "Clear {} lines", lines),
+            Goal::PerfectClear => write!(f, "Clear the board"),
+            Goal::TSpins(1) => write!(f, "Clear with a T-Spin"),
+            Goal::TSpins(t_spins) => write!(f, "Clear with {} T-Spins", t_spins),
+            Goal::TSpinDoubles(1) => write!(f, "Do a T-Spin Double"),
+            Goal::TSpinDoubles(t_spins) => write!(f, "Do {} T-Spin Doubles", t_spins),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PuzzleOutcome {
+    Solved,
+    Failed,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Level {
+    /// File name without extension. Used to remember solved levels.
+    pub id: String,
+    pub name: String,
+    pub goal: Goal,
+    pub pieces: Vec<PieceKind>,
+    /// Rows of the board from top to bottom. Missing rows at the top are empty.
+    rows: Vec<Vec<Option<Cell>>>,
+}
+
+impl Level {
+    pub fn parse(id: &str, contents: &str) -> Result<Self, String> {
+        let mut name = None;
+        let mut goal = None;
+        let mut pieces = None;
+        let mut rows = vec![];
+        let mut in_board = false;
+
+        for (line_number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            // The board has to come last since '#' is garbage in there
+            if line.is_empty() || (!in_board && line.starts_with('#')) {
+                continue;
+            }
+            let error = |err: String| format!("Line {}: {}", line_number + 1, err);
+
+            if in_board {
+                rows.push(line.chars().map(parse_cell).collect());
+                continue;
+            }
+            let (key, value) = line.split_once(':')
+                .ok_or_else(|| error(format!("Expected \"key: value\" but got \"{}\"", line)))?;
+            let value = value.trim();
+            match key.trim() {
+                "name" => name = Some(value.to_owned()),
+                "goal" => goal = Some(parse_goal(value).map_err(error)?),
+                "pieces" => pieces = Some(value.split_whitespace().map(parse_piece).collect::<Result<Vec<_>, _>>().map_err(error)?),
+                "board" => in_board = true,
+                key => return Err(error(format!("Unknown key \"{}\"", key))),
+            }
+        }
+
+        let pieces = pieces.ok_or("Missing pieces")?;
+        if pieces.is_empty() {
+            return Err("No pieces given".to_owned());
+        }
+        Ok(Self {
+            id: id.to_owned(),
+            name: name.unwrap_or_else(|| id.to_owned()),
+            goal: goal.ok_or("Missing goal")?,
+            pieces,
+            rows,
+        })
+    }
+
+    /// The prepared board for a playfield of the given size.
+    pub fn board(&self, size: &Size) -> Result<Board, String> {
+        if self.rows.len() > size.height {
+            return Err(format!("Board has {} rows but only {} fit", self.rows.len(), size.height));
+        }
+        let mut board = Board::new(size.width, size.height);
+        let top = size.height - self.rows.len();
+        for (y, row) in self.rows.iter().enumerate() {
+            if row.len() != size.width {
+                return Err(format!("Row {} of the board has {} cells instead of {}", y + 1, row.len(), size.width));
+            }
+            for (x, cell) in row.iter().enumerate() {
+                board.set(x, top + y, *cell);
+            }
+        }
+        Ok(board)
+    }
+}
+
+/// Writes a board in the level format, e.g. to turn a game into a puzzle.
+pub fn level_text(name: &str, goal: Goal, pieces: &[PieceKind], board: &Board) -> String {
+    let goal = match goal {
+        Goal::Lines(lines) => format!("lines {}", lines),
+        Goal::PerfectClear => "perfect_clear".to_owned(),
+        Goal::TSpins(t_spins) => format!("t_spin {}", t_spins),
+        Goal::TSpinDoubles(t_spins) => format!("t_spin_double {}", t_spins),
+    };
+    let pieces: Vec<String> = pieces.iter().map(|kind| format!("{:?}", kind)).collect();
+    let mut text = format!("name: {}\ngoal: {}\npieces: {}\nboard:\n", name, goal, pieces.join(" "));
+
+    // Empty rows at the top can be left out
+    let first_row = (0..board.height())
+        .find(|&y| (0..board.width()).any(|x| board.get(x, y).is_some()))
+        .unwrap_or(board.height() - 1);
+    for y in first_row..board.height() {
+        for x in 0..board.width() {
+            text.push(match board.get(x, y) {
+                None => '.',
+                Some(Cell::Piece(kind)) => format!("{:?}", kind).chars().next().unwrap(),
+                Some(Cell::Garbage) => '#',
+            });
+        }
+        text.push('\n');
+    }
+    text
+}
+
+fn parse_cell(c: char) -> Option<Cell> {
+    match c {
+        '.' => None,
+        c => Some(parse_piece(&c.to_string()).map(Cell::Piece).unwrap_or(Cell::Garbage)),
+    }
+}
+
+fn parse_piece(value: &str) -> Result<PieceKind, String> {
+    match value {
+        "I" => Ok(PieceKind::I),
+        "J" => Ok(PieceKind::J),
+        "L" => Ok(PieceKind::L),
+        "O" => Ok(PieceKind::O),
+        "S" => Ok(PieceKind::S),
+        "T" => Ok(PieceKind::T),
+        "Z" => Ok(PieceKind::Z),
+        _ => Err(format!("Unknown piece \"{}\"", value)),
+    }
+}
+
+fn parse_goal(value: &str) -> Result<Goal, String> {
+    let parts: Vec<&str> = value.split_whitespace().collect();
+    let count = || -> Result<u64, String> {
+        parts.get(1).unwrap_or(&"1").parse().map_err(|err| format!("Invalid count in goal \"{}\": {}", value, err))
+    };
+    match parts.first() {
+        Some(&"lines") => Ok(Goal::Lines(count()?)),
+        Some(&"perfect_clear") => Ok(Goal::PerfectClear),
+        Some(&"t_spin") => Ok(Goal::TSpins(count()?)),
+        Some(&"t_spin_double") => Ok(Goal::TSpinDoubles(count()?)),
+        _ => Err(format!("Unknown goal \"{}\". Expected lines, perfect_clear, t_spin or t_spin_double", value)),
+    }
+}
+
+fn user_levels_dir() -> PathBuf {
+    Config::path().with_file_name("levels")
+}
+
+/// All built-in levels followed by the ones of the user (sorted by file name).
+/// Broken levels and ones that don't fit the playfield are left out. Their
+/// errors get returned as well to show them.
+pub fn levels(size: &Size) -> (Vec<Level>, Vec<String>) {
+    let mut sources: Vec<(String, String)> = BUILTIN_LEVELS.iter()
+        .map(|(id, contents)| (id.to_string(), contents.to_string()))
+        .collect();
+    let mut errors = vec![];
+
+    if let Ok(entries) = fs::read_dir(user_levels_dir()) {
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().map(|ext| ext == "level").unwrap_or(false))
+            .collect();
+        paths.sort();
+        for path in paths {
+            let id = path.file_stem().unwrap().to_string_lossy().into_owned();
+            match fs::read_to_string(&path) {
+                Ok(contents) => sources.push((id, contents)),
+                Err(err) => errors.push(format!("Failed to read level {}: {}", id, err)),
+            }
+        }
+    }
+
+    let mut levels = vec![];
+    for (id, contents) in sources {
+        match Level::parse(&id, &contents).and_then(|level| level.board(size).map(|_| level)) {
+            Ok(level) => levels.push(level),
+            Err(err) => errors.push(format!("Level {} is invalid: {}", id, err)),
+        }
+    }
+    for error in errors.iter() {
+        eprintln!("{}", error);
+    }
+    (levels, errors)
+}
+
+fn solved_path() -> PathBuf {
+    Config::path().with_file_name("puzzles_solved")
+}
+
+/// Ids of all levels that were solved at least once.
+pub fn solved_levels() -> Vec<String> {
+    fs::read_to_string(solved_path())
+        .map(|contents| contents.lines().map(|line| line.trim().to_owned()).filter(|line| !line.is_empty()).collect())
+        .unwrap_or_default()
+}
+
+pub fn mark_solved(level: &Level) -> io::Result<()> {
+    let mut solved = solved_levels();
+    if solved.contains(&level.id) {
+        return Ok(());
+    }
+    solved.push(level.id.clone());
+    let path = solved_path();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, solved.join("\n") + "\n")
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,112 @@
+use super::*;
+
+const SIZE: Size = Size { width: 10, height: 22 };
+
+const EXAMPLE: &str = "\
+# Comment
+name: First T-Spin
+goal: t_spin 1
+pieces: T I
+board:
+..........
+###...####
+####.#T###
+";
+
+#[test]
+fn levels_get_parsed() {
+    let level = Level::parse("example", EXAMPLE).unwrap();
+    assert_eq!(level.id, "example");
+    assert_eq!(level.name, "First T-Spin");
+    assert_eq!(level.goal, Goal::TSpins(1));
+    assert_eq!(level.pieces, vec![PieceKind::T, PieceKind::I]);
+
+    let board = level.board(&SIZE).unwrap();
+    assert!((0..10).all(|x| board.get(x, 19).is_none()));
+    assert_eq!(board.get(0, 20), Some(Cell::Garbage));
+    assert_eq!(board.get(3, 20), None);
+    assert_eq!(board.get(4, 21), None);
+    assert_eq!(board.get(6, 21), Some(Cell::Piece(PieceKind::T)));
+    assert!((0..19).all(|y| (0..10).all(|x| board.get(x, y).is_none())));
+
+    // The name defaults to the id
+    let level = Level::parse("unnamed", "goal: perfect_clear\npieces: O\n").unwrap();
+    assert_eq!(level.name, "unnamed");
+    assert!(level.board(&SIZE).unwrap().is_empty());
+}
+
+#[test]
+fn goals_get_parsed() {
+    let cases = [
+        ("lines 3", Goal::Lines(3)),
+        ("lines", Goal::Lines(1)),
+        ("perfect_clear", Goal::PerfectClear),
+        ("t_spin 2", Goal::TSpins(2)),
+        ("t_spin_double 1", Goal::TSpinDoubles(1)),
+    ];
+    for (value, goal) in cases.iter() {
+        assert_eq!(parse_goal(value).as_ref(), Ok(goal));
+    }
+    for value in ["", "lines many", "t_spin -1", "tetris"].iter() {
+        assert!(parse_goal(value).is_err(), "{:?} was accepted", value);
+    }
+}
+
+#[test]
+fn broken_levels_are_rejected() {
+    let contents = [
+        "pieces: T\n",
+        "goal: lines 1\n",
+        "goal: lines 1\npieces:\n",
+        "goal: lines 1\npieces: T X\n",
+        "goal: sprint\npieces: T\n",
+        "goal: lines 1\npieces: T\nspeed: 2\n",
+        "goal: lines 1\npieces: T\nno key\n",
+    ];
+    for contents in contents.iter() {
+        assert!(Level::parse("broken", contents).is_err(), "{:?} was accepted", contents);
+    }
+
+    // Boards that don't fit the playfield
+    let level = Level::parse("wide", "goal: lines 1\npieces: T\nboard:\n###########\n").unwrap();
+    assert!(level.board(&SIZE).is_err());
+    let level = Level::parse("narrow", "goal: lines 1\npieces: T\nboard:\n##########\n#########\n").unwrap();
+    assert!(level.board(&SIZE).is_err());
+    let rows = vec!["#########."; 23].join("\n");
+    let level = Level::parse("high", &format!("goal: lines 1\npieces: T\nboard:\n{}\n", rows)).unwrap();
+    assert!(level.board(&SIZE).is_err());
+}
+
+#[test]
+fn level_text_survives_a_round_trip() {
+    let goals = [Goal::Lines(2), Goal::PerfectClear, Goal::TSpins(1), Goal::TSpinDoubles(3)];
+    let mut board = Board::new(10, 22);
+    board.set(0, 21, Some(Cell::Garbage));
+    board.set(9, 21, Some(Cell::Piece(PieceKind::L)));
+    board.set(4, 18, Some(Cell::Piece(PieceKind::Z)));
+    let pieces = [PieceKind::I, PieceKind::S, PieceKind::T];
+
+    for goal in goals.iter() {
+        let text = level_text("Saved", *goal, &pieces, &board);
+        let level = Level::parse("saved", &text).unwrap();
+        assert_eq!(level.name, "Saved");
+        assert_eq!(level.goal, *goal);
+        assert_eq!(level.pieces, pieces);
+        assert_eq!(level.board(&SIZE).unwrap(), board);
+    }
+    // Empty rows at the top are left out
+    assert!(level_text("Saved", Goal::Lines(1), &pieces, &board).ends_with("board:\n....Z.....\n..........\n..........\n#........L\n"));
+
+    let empty = Board::new(10, 22);
+    let level = Level::parse("empty", &level_text("Empty", Goal::Lines(1), &pieces, &empty)).unwrap();
+    assert_eq!(level.board(&SIZE).unwrap(), empty);
+}
+
+#[test]
+fn builtin_levels_are_valid() {
+    for (id, contents) in BUILTIN_LEVELS.iter() {
+        let level = Level::parse(id, contents).unwrap_or_else(|err| panic!("{}: {}", id, err));
+        let board = level.board(&SIZE).unwrap_or_else(|err| panic!("{}: {}", id, err));
+        assert!(!board.is_empty(), "{}", id);
+    }
+}
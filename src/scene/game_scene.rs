@@ -1,16 +1,23 @@
-use super::Scene;
+use super::{Scene, FRAME_DURATION};
 use crate::canvas::*;
-use crate::swipe::{SwipeTracker, Swipe, Trigger, Direction};
+use crate::config::{Config, ControlScheme, RefreshPolicy};
+use crate::engine::{Randomizer, Game, LockDelayConfig, Size, Cell, Piece, PieceKind, Action, TSpin, TSpinClear};
+use crate::hold::{HoldTracker, HoldSource, HoldEvent};
+use crate::puzzle::{self, Goal, Level, PuzzleOutcome};
+use crate::status_bar::StatusBar;
+use crate::tap_zones::{TapZonesConfig, TapZone};
+use crate::swipe::{DragTracker, MultiTapTracker, SwipeTracker, Swipe, Trigger, Direction};
+use crate::versus::{self, Connection, Message, VersusOutcome};
+use crate::widget::*;
 use fxhash::FxHashMap;
 use libremarkable::image::RgbImage;
 use libremarkable::device::{CURRENT_DEVICE, Model};
 use std::cell::RefCell;
 use std::collections::HashMap;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use std::sync::{Arc, atomic::{AtomicU32, Ordering}};
 use libremarkable::input::{Finger, GPIOEvent, InputEvent, MultitouchEvent, PhysicalButton};
-use {rand, rand::Rng};
-use tetris_core::{Randomizer, Game, Size, Block, Action};
+use rand::Rng;
 
 struct OpionatedRandomizer {
     /// Since the trait gives only immutable self,
@@ -42,10 +49,10 @@ impl OpionatedRandomizer {
 impl Randomizer for OpionatedRandomizer {
     fn random_between(&self, first: i32, last: i32) -> i32 {
         if first == 0 && last == 6 {
-            // Basicially the only thing tetris_core will ever want
+            // Basicially the only thing the game will ever want
 
             // Fillup the pool again
-            if self.block_pool.borrow().len() == 0 {
+            if self.block_pool.borrow().is_empty() {
                 self.fillup(2);
             }
 
@@ -60,38 +67,120 @@ impl Randomizer for OpionatedRandomizer {
     }
 }
 
-#[derive(Hash, Eq, PartialEq)]
-struct StupidColor(u8, u8, u8);
-
-impl StupidColor {
-    pub fn from(r: f32, g: f32, b: f32) -> Self {
-        StupidColor(
-            (r * 255.0) as u8,
-            (g * 255.0) as u8,
-            (b * 255.0) as u8
-        )
+/// Gives out the pieces of a puzzle level in order.
+struct ScriptedRandomizer {
+    pieces: Vec<PieceKind>,
+    next: RefCell<usize>,
+    /// Same as OpionatedRandomizer.block_id
+    block_id: Arc<AtomicU32>,
+}
+impl ScriptedRandomizer {
+    pub fn new(pieces: Vec<PieceKind>, block_id: Arc<AtomicU32>) -> Self {
+        Self { pieces, next: RefCell::new(0), block_id }
+    }
+}
+impl Randomizer for ScriptedRandomizer {
+    fn random_between(&self, first: i32, last: i32) -> i32 {
+        if first == 0 && last == 6 {
+            // The game asks for a few pieces more than the level has.
+            // Those never get played since the level ends before.
+            let index = *self.next.borrow();
+            *self.next.borrow_mut() += 1;
+            self.block_id.fetch_add(1, Ordering::Relaxed);
+            self.pieces.get(index).unwrap_or(&PieceKind::O).index()
+        }else {
+            first
+        }
     }
 }
 
+/// Puzzles are about thinking, not speed.
+const PUZZLE_SPEED: f64 = 0.5;
+
+/// Flicking down faster than this (px/s) drops the block all the way.
+/// Slower swipes only move it down row by row.
+const HARD_DROP_MIN_VELOCITY: u16 = 1000;
+/// Flicking up faster than this (px/s) turns the block around.
+/// Slower swipes rotate it counter-clockwise.
+const HALF_ROTATION_MIN_VELOCITY: u16 = 1000;
+
+/// Movements that repeat while held down.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Shift { Left, Right }
+
+/// Buttons at the top and of the pause overlay.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum HudAction { PlayPause, MainMenu, Restart, Settings }
+
+/// What is shown in a cell of the playfield.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Tile {
+    Block(Cell),
+    /// Where the active piece would land
+    Ghost,
+}
+
+/// Size of the cells in the preview of the next pieces.
+const QUEUE_CELL_SIZE: u32 = 25;
+/// How long a T-spin is announced next to the score.
+const CALLOUT_DURATION: Duration = Duration::from_secs(2);
+
 pub struct GameScene {
     game: Game,
     speed: f64,
     last_draw: Option<Instant>,
     game_size: Size,
     block_size: usize,
-    last_blocks: HashMap<Point2<u8>, Block>,
+    last_blocks: HashMap<Point2<u8>, Tile>,
     last_score: u64,
-    textures: HashMap<StupidColor, RgbImage>,
+    last_queue: Vec<PieceKind>,
+    /// Announcement of the last T-spin and when it happened.
+    callout: Option<(String, Instant)>,
+    callout_changed: bool,
+    textures: HashMap<PieceKind, RgbImage>,
     swipe_tracker: SwipeTracker,
+    drag_tracker: DragTracker,
+    hold_tracker: HoldTracker<Shift>,
     last_pressed_finger: Option<(Finger, Instant)>,
-    play_pause_button_hitbox: Option<mxcfb_rect>,
-    back_button_hitbox: Option<mxcfb_rect>,
-    left_button_hitbox: Option<mxcfb_rect>,
-    right_button_hitbox: Option<mxcfb_rect>,
+    play_pause_button: Toggle<HudAction>,
+    main_menu_button: Button<HudAction>,
+    restart_button: Button<HudAction>,
+    left_button: Button<Shift>,
+    right_button: Button<Shift>,
     is_paused: bool,
+    /// Pause overlay needs to be drawn or removed.
+    overlay_changed: bool,
+    pause_menu: List<HudAction>,
     pub back_button_pressed: bool,
+    pub settings_button_pressed: bool,
     block_id: Arc<AtomicU32>,
     finger_controls_which_block: FxHashMap<i32/* Tracking id */, u32/* Block id */>,
+    /// Tapping with three fingers at once restarts.
+    restart_tap_tracker: MultiTapTracker,
+    /// Playfield needs to be drawn from scratch (e.g. after restarting).
+    field_changed: bool,
+    control_scheme: ControlScheme,
+    tap_zones: TapZonesConfig,
+    arrow_buttons: bool,
+    ghost_piece: bool,
+    block_patterns: bool,
+    refresh_policy: RefreshPolicy,
+    versus: Option<Connection>,
+    versus_outcome: Option<VersusOutcome>,
+    /// Lines already accounted for when sending garbage.
+    versus_lines_cleared: u64,
+    /// Board as last sent to the opponent.
+    versus_sent_cells: Vec<bool>,
+    last_pending_garbage: u32,
+    opponent_cells: Vec<bool>,
+    opponent_cells_drawn: Vec<bool>,
+    puzzle: Option<Level>,
+    puzzle_outcome: Option<PuzzleOutcome>,
+    /// T-spins that cleared lines so far (for Goal::TSpins)
+    puzzle_t_spins: u64,
+    /// T-spins that cleared two or more lines (for Goal::TSpinDoubles)
+    puzzle_t_spin_doubles: u64,
+    status_bar: StatusBar,
 }
 
 
@@ -99,7 +188,7 @@ impl GameScene {
     pub fn new(game_size: Size, speed: f64) -> Self {
         // Generate textures
         let block_size = 50;
-        let mut textures: HashMap<StupidColor, RgbImage> = HashMap::new();
+        let mut textures: HashMap<PieceKind, RgbImage> = HashMap::new();
         let black = libremarkable::image::Rgb([0, 0, 0]);
         let white = libremarkable::image::Rgb([255, 255, 255]);
         let img_i: RgbImage = RgbImage::from_fn(block_size, block_size, |x, y|
@@ -123,34 +212,195 @@ impl GameScene {
         let img_s: RgbImage = RgbImage::from_fn(block_size, block_size, |x, y|
             if x * y % 5 != 0 { black } else { white }
         );
-        textures.insert(StupidColor::from(108.0 / 255.0, 237.0 / 255.0, 238.0 / 255.0), img_i); // I-Block
-        textures.insert(StupidColor::from(0.0, 33.0 / 255.0, 230.0 / 255.0), img_j); // J-Block
-        textures.insert(StupidColor::from(229.0 / 255.0, 162.0 / 255.0, 67.0 / 255.0), img_l); // L-Block
-        textures.insert(StupidColor::from(241.0 / 255.0, 238.0 / 255.0, 79.0 / 255.0), img_o); // O-Block
-        textures.insert(StupidColor::from(110.0 / 255.0, 235.0 / 255.0, 71.0 / 255.0), img_z); // Z-Block
-        textures.insert(StupidColor::from(146.0 / 255.0, 45.0 / 255.0, 231.0 / 255.0), img_t); // T-Block
-        textures.insert(StupidColor::from(221.0 / 255.0, 47.0 / 255.0, 23.0 / 255.0), img_s); // S-Block
+        textures.insert(PieceKind::I, img_i);
+        textures.insert(PieceKind::J, img_j);
+        textures.insert(PieceKind::L, img_l);
+        textures.insert(PieceKind::O, img_o);
+        textures.insert(PieceKind::Z, img_z);
+        textures.insert(PieceKind::T, img_t);
+        textures.insert(PieceKind::S, img_s);
 
+        let config = Config::current();
         let block_id = Arc::new(AtomicU32::new(0));
         Self {
-            game: Game::new(&Size { width: 10, height: 22 }, Box::new(OpionatedRandomizer::new(block_id.clone()))),
+            game: Self::create_game(&game_size, speed, block_id.clone(), config.lock_delay),
             speed,
             last_draw: None,
             game_size,
             block_size: block_size as usize,
             last_blocks: HashMap::new(),
             last_score: 0,
+            last_queue: vec![],
+            callout: None,
+            callout_changed: false,
             textures,
-            swipe_tracker: SwipeTracker::new(),
+            swipe_tracker: SwipeTracker::new(config.swipe),
+            drag_tracker: DragTracker::new(config.swipe),
+            hold_tracker: HoldTracker::new(config.hold),
             last_pressed_finger: None,
-            play_pause_button_hitbox: None,
-            back_button_hitbox: None,
-            left_button_hitbox: None,
-            right_button_hitbox: None,
+            play_pause_button: Toggle::new("Pause", "Resume", 50.0, HudAction::PlayPause).with_gaps(10, 20),
+            main_menu_button: Button::new("Main Menu", 50.0, HudAction::MainMenu).with_gaps(10, 20),
+            restart_button: Button::new("Restart", 50.0, HudAction::Restart).with_gaps(10, 20),
+            left_button: Button::new("«", 100.0, Shift::Left).with_gaps(50, 50),
+            right_button: Button::new("»", 100.0, Shift::Right).with_gaps(50, 50),
             is_paused: false,
+            overlay_changed: false,
+            pause_menu: List::new(90)
+                .with_button(Button::new("Resume", 60.0, HudAction::PlayPause).with_gaps(20, 30))
+                .with_button(Button::new("Restart", 60.0, HudAction::Restart).with_gaps(20, 30))
+                .with_button(Button::new("Settings", 60.0, HudAction::Settings).with_gaps(20, 30))
+                .with_button(Button::new("Quit to menu", 60.0, HudAction::MainMenu).with_gaps(20, 30)),
             back_button_pressed: false,
+            settings_button_pressed: false,
             block_id,
             finger_controls_which_block: FxHashMap::default(),
+            restart_tap_tracker: MultiTapTracker::new(3, config.swipe),
+            field_changed: false,
+            control_scheme: config.control_scheme,
+            tap_zones: config.tap_zones,
+            arrow_buttons: config.arrow_buttons,
+            ghost_piece: config.ghost_piece,
+            block_patterns: config.block_patterns,
+            refresh_policy: config.refresh_policy,
+            versus: None,
+            versus_outcome: None,
+            versus_lines_cleared: 0,
+            versus_sent_cells: vec![],
+            last_pending_garbage: 0,
+            opponent_cells: vec![],
+            opponent_cells_drawn: vec![],
+            puzzle: None,
+            puzzle_outcome: None,
+            puzzle_t_spins: 0,
+            puzzle_t_spin_doubles: 0,
+            status_bar: StatusBar::from_config(&config),
+        }
+    }
+
+    pub fn new_versus(game_size: Size, speed: f64, connection: Connection) -> Self {
+        let mut game_scene = Self::new(game_size, speed);
+        game_scene.versus = Some(connection);
+        game_scene
+    }
+
+    /// Fails if the board of the level doesn't fit the game size.
+    pub fn new_puzzle(game_size: Size, level: Level) -> Result<Self, String> {
+        let mut game_scene = Self::new(game_size, PUZZLE_SPEED);
+        game_scene.block_id.store(0, Ordering::Relaxed);
+        game_scene.game = Self::create_puzzle_game(&game_size, game_scene.speed, &level, game_scene.block_id.clone(), Config::current().lock_delay)?;
+        game_scene.puzzle = Some(level);
+        Ok(game_scene)
+    }
+
+    /// A new game with an empty board and random pieces.
+    fn create_game(game_size: &Size, speed: f64, block_id: Arc<AtomicU32>, lock_delay: LockDelayConfig) -> Game {
+        Game::new(game_size, speed, lock_delay, Box::new(OpionatedRandomizer::new(block_id)))
+    }
+
+    /// A new game with the prepared board and pieces of the puzzle.
+    fn create_puzzle_game(game_size: &Size, speed: f64, level: &Level, block_id: Arc<AtomicU32>, lock_delay: LockDelayConfig) -> Result<Game, String> {
+        let board = level.board(game_size).map_err(|err| format!("{} is invalid: {}", level.name, err))?;
+        let mut game = Game::new(game_size, speed, lock_delay, Box::new(ScriptedRandomizer::new(level.pieces.clone(), block_id)));
+        game.set_board(board);
+        Ok(game)
+    }
+
+    /// Takes over changed settings. Everything gets drawn
+    /// again the next time the scene is shown.
+    pub fn apply_config(&mut self, config: &Config) {
+        self.game.set_lock_delay(config.lock_delay);
+        self.swipe_tracker.set_config(config.swipe);
+        self.drag_tracker.set_config(config.swipe);
+        self.restart_tap_tracker.set_config(config.swipe);
+        self.hold_tracker = HoldTracker::new(config.hold);
+        self.control_scheme = config.control_scheme;
+        self.tap_zones = config.tap_zones;
+        self.arrow_buttons = config.arrow_buttons;
+        self.ghost_piece = config.ghost_piece;
+        self.block_patterns = config.block_patterns;
+        self.refresh_policy = config.refresh_policy;
+    }
+
+    /// Versus games can't be restarted alone.
+    fn can_restart(&self) -> bool {
+        self.versus.is_none()
+    }
+
+    /// Starts over with the same settings. Only the playfield
+    /// gets redrawn, everything around it stays.
+    fn restart(&mut self) {
+        if !self.can_restart() {
+            return;
+        }
+        self.block_id.store(0, Ordering::Relaxed);
+        let lock_delay = Config::current().lock_delay;
+        let game = match self.puzzle {
+            Some(ref level) => Self::create_puzzle_game(&self.game_size, self.speed, level, self.block_id.clone(), lock_delay),
+            None => Ok(Self::create_game(&self.game_size, self.speed, self.block_id.clone(), lock_delay)),
+        };
+        match game {
+            Ok(game) => self.game = game,
+            Err(err) => {
+                // Same level and size as before, so it can't really happen
+                eprintln!("Failed to restart: {}", err);
+                return;
+            },
+        }
+        self.puzzle_outcome = None;
+        self.puzzle_t_spins = 0;
+        self.puzzle_t_spin_doubles = 0;
+        if self.callout.take().is_some() {
+            self.callout_changed = true;
+        }
+        // No finger on the screen should affect the new game
+        self.hold_tracker.clear();
+        self.finger_controls_which_block.clear();
+        self.last_pressed_finger = None;
+
+        if self.is_paused {
+            // Redraws the playfield as well
+            self.set_paused(false);
+        }else {
+            self.field_changed = true;
+        }
+    }
+
+    /// Result of a puzzle once it is solved or failed.
+    pub fn puzzle_outcome(&self) -> Option<PuzzleOutcome> {
+        self.puzzle_outcome
+    }
+
+    pub fn puzzle(&self) -> Option<&Level> {
+        self.puzzle.as_ref()
+    }
+
+    fn update_puzzle(&mut self) {
+        let level = match self.puzzle {
+            Some(ref level) if self.puzzle_outcome.is_none() => level,
+            _ => return,
+        };
+        let solved = match level.goal {
+            Goal::Lines(lines) => self.game.lines_cleared() >= lines,
+            Goal::PerfectClear => self.game.pieces_placed() > 0 && self.game.board().is_empty(),
+            Goal::TSpins(t_spins) => self.puzzle_t_spins >= t_spins,
+            Goal::TSpinDoubles(t_spins) => self.puzzle_t_spin_doubles >= t_spins,
+        };
+        if solved {
+            if let Err(err) = puzzle::mark_solved(level) {
+                eprintln!("Failed to remember solved level {}: {}", level.id, err);
+            }
+            self.puzzle_outcome = Some(PuzzleOutcome::Solved);
+        }else if self.game.is_game_over() || self.game.pieces_placed() >= level.pieces.len() as u64 {
+            self.puzzle_outcome = Some(PuzzleOutcome::Failed);
+        }
+    }
+
+    /// How many of the next pieces can be shown.
+    fn visible_queue_len(&self) -> usize {
+        match self.puzzle {
+            // Don't show the filler pieces after the last one
+            Some(ref level) => (level.pieces.len() as u64).saturating_sub(self.game.pieces_placed() + 1) as usize,
+            None => usize::MAX,
         }
     }
 
@@ -166,7 +416,7 @@ impl GameScene {
     }
 
     pub fn game_size(&self) -> Size {
-        self.game_size.clone()
+        self.game_size
     }
 
     pub fn field_start_i32(&self) -> Point2<i32> {
@@ -192,12 +442,296 @@ impl GameScene {
         self.game.get_score()
     }
 
+    fn set_paused(&mut self, paused: bool) {
+        if paused == self.is_paused {
+            return;
+        }
+        self.is_paused = paused;
+        self.overlay_changed = true;
+        self.hold_tracker.clear();
+        // Fingers from before shouldn't move the block afterwards
+        self.finger_controls_which_block.clear();
+    }
+
+    /// Whether the finger was only pressed shortly and didn't move.
+    fn is_tap(&self, up_finger: &Finger) -> bool {
+        match self.last_pressed_finger {
+            Some((down_finger, down_when)) if down_finger.tracking_id == up_finger.tracking_id => {
+                let x_dist = up_finger.pos.x as i32 - down_finger.pos.x as i32;
+                let y_dist = up_finger.pos.y as i32 - down_finger.pos.y as i32;
+                let dist = (x_dist.pow(2) as f32 + y_dist.pow(2) as f32).sqrt();
+                down_when.elapsed().as_millis() < 300 && dist < 20.0
+            },
+            _ => false,
+        }
+    }
+
+    /// Every button needs to see all touch events to follow presses.
+    fn on_touch(&mut self, event: &MultitouchEvent) -> Option<HudAction> {
+        if !self.is_paused {
+            // Arrow buttons already act when pressed (see on_input())
+            self.left_button.on_touch(event);
+            self.right_button.on_touch(event);
+        }
+        [
+            self.play_pause_button.on_touch(event),
+            self.main_menu_button.on_touch(event),
+            self.restart_button.on_touch(event),
+            self.pause_menu.on_touch(event),
+        ].iter().find_map(|action| *action)
+    }
+
+    fn on_hud_action(&mut self, action: HudAction) {
+        match action {
+            HudAction::PlayPause => self.set_paused(!self.is_paused),
+            HudAction::Restart => self.restart(),
+            HudAction::Settings => self.settings_button_pressed = true,
+            HudAction::MainMenu => self.back_button_pressed = true,
+        }
+    }
+
+    fn on_hold_event(&mut self, event: HoldEvent<Shift>) {
+        match event {
+            HoldEvent::Start(Shift::Left) | HoldEvent::Repeat(Shift::Left) => self.game.perform(Action::MoveLeft),
+            HoldEvent::Start(Shift::Right) | HoldEvent::Repeat(Shift::Right) => self.game.perform(Action::MoveRight),
+            HoldEvent::End(_) => false,
+        };
+    }
+
+    /// Which shift the software arrow button at pos would do.
+    fn arrow_button_at(&self, pos: Point2<u16>) -> Option<Shift> {
+        self.left_button.hit(pos).or_else(|| self.right_button.hit(pos))
+    }
+
+
+    /// Column of the playfield at the given x coordinate.
+    /// Can be outside of the playfield.
+    fn column_at(&self, x: u16) -> i32 {
+        (x as i32 - self.field_start_i32().x).div_euclid(self.block_size as i32)
+    }
+
+    /// Moves the current block until its center is in the column
+    /// at x or a wall is hit (ControlScheme::Drag).
+    fn drag(&mut self, tracking_id: i32, x: u16) {
+        if self.finger_controls_which_block.get(&tracking_id) != Some(&self.block_id.load(Ordering::Relaxed)) {
+            // Only the block that was current when pressing can be dragged
+            return;
+        }
+        let column = self.column_at(x);
+        loop {
+            let cells = self.game.active().cells();
+            let min_x = cells.iter().map(|(x, _)| *x).min().unwrap();
+            let max_x = cells.iter().map(|(x, _)| *x).max().unwrap();
+            let center = (min_x + max_x) / 2;
+            let moved = if column < center {
+                self.game.perform(Action::MoveLeft)
+            }else if column > center {
+                self.game.perform(Action::MoveRight)
+            }else {
+                false
+            };
+            if !moved {
+                break;
+            }
+        }
+    }
+
+    fn on_tap_zone(&mut self, zone: TapZone) {
+        match zone {
+            TapZone::Left => self.game.perform(Action::MoveLeft),
+            TapZone::Right => self.game.perform(Action::MoveRight),
+            TapZone::Rotate => self.game.perform(Action::RotateCw),
+            TapZone::Drop => self.game.perform(Action::HardDrop),
+        };
+    }
+
+    /// Faintly draws the borders of the tap zones around
+    /// the playfield and score.
+    fn draw_tap_zones(&self, canvas: &mut Canvas) {
+        let field_start = self.field_start_u32();
+        let field_size = self.field_size();
+        let hole = mxcfb_rect {
+            left: field_start.x - 2,
+            top: field_start.y - 2,
+            width: 2 + field_size.x + 2,
+            height: 2 + field_size.y + 100,
+        };
+        for border in self.tap_zones.borders(2) {
+            let border_bottom = border.top + border.height;
+            let hole_bottom = hole.top + hole.height;
+            let overlaps_horizontally = border.left < hole.left + hole.width && hole.left < border.left + border.width;
+            let parts = if overlaps_horizontally && border.top < hole_bottom && hole.top < border_bottom {
+                // Leave out the playfield
+                vec![
+                    mxcfb_rect { height: hole.top.saturating_sub(border.top), ..border },
+                    mxcfb_rect { top: hole_bottom, height: border_bottom.saturating_sub(hole_bottom), ..border },
+                ]
+            }else {
+                vec![border]
+            };
+            let gray = canvas.theme().gray(64);
+            for part in parts.into_iter().filter(|part| part.height > 0) {
+                canvas.framebuffer_mut().fill_rect(
+                    Point2 { x: part.left as i32, y: part.top as i32 },
+                    Vector2 { x: part.width, y: part.height },
+                    gray
+                );
+            }
+        }
+    }
+
+    /// Result of a versus game once it is decided.
+    pub fn versus_outcome(&self) -> Option<VersusOutcome> {
+        self.versus_outcome
+    }
+
+    /// Exchanges board, garbage and game over with the opponent.
+    /// Returns true if the opponents board changed.
+    fn update_versus(&mut self) -> bool {
+        let connection = match self.versus {
+            Some(ref mut connection) if self.versus_outcome.is_none() => connection,
+            _ => return false,
+        };
+
+        if self.game.is_game_over() {
+            connection.send(&Message::GameOver { score: self.game.get_score() });
+            self.versus_outcome = Some(VersusOutcome::Lost);
+            return false;
+        }
+
+        let lines_cleared = self.game.lines_cleared();
+        if lines_cleared > self.versus_lines_cleared {
+            let garbage = versus::garbage_for_lines((lines_cleared - self.versus_lines_cleared) as u32);
+            self.versus_lines_cleared = lines_cleared;
+            // Cancel out incoming garbage first
+            let canceled = self.game.cancel_garbage(garbage);
+            if garbage > canceled {
+                connection.send(&Message::Garbage { lines: garbage - canceled });
+            }
+        }
+
+        // Only the locked cells, the opponent doesn't need every move
+        let board = self.game.board();
+        let cells: Vec<bool> = (0..board.height())
+            .flat_map(|y| (0..board.width()).map(move |x| board.get(x, y).is_some()))
+            .collect();
+        if cells != self.versus_sent_cells {
+            connection.send(&Message::Board { width: board.width(), height: board.height(), cells: cells.clone() });
+            self.versus_sent_cells = cells;
+        }
+
+        let mut opponent_changed = false;
+        while let Some(message) = connection.try_recv() {
+            match message {
+                Message::Hello { version } => {
+                    if version != versus::PROTOCOL_VERSION {
+                        eprintln!("Versus: Opponent uses protocol version {} instead of {}", version, versus::PROTOCOL_VERSION);
+                        self.versus_outcome = Some(VersusOutcome::OpponentLeft);
+                    }
+                },
+                Message::Board { width, height, cells } => {
+                    if width == self.game_size.width && height == self.game_size.height {
+                        self.opponent_cells = cells;
+                        opponent_changed = true;
+                    }
+                },
+                Message::Garbage { lines } => self.game.queue_garbage(lines),
+                Message::GameOver { .. } => self.versus_outcome = Some(VersusOutcome::Won),
+            }
+        }
+
+        if connection.is_disconnected() && self.versus_outcome.is_none() {
+            self.versus_outcome = Some(VersusOutcome::OpponentLeft);
+        }
+
+        opponent_changed
+    }
+
+    fn opponent_field_start(&self) -> Point2<i32> {
+        let field_start = self.field_start_i32();
+        Point2 { x: field_start.x + self.field_size().x as i32 + 50, y: field_start.y }
+    }
+
+    /// Draws the opponents board in small next to the own one.
+    fn draw_opponent(&mut self, canvas: &mut Canvas) -> mxcfb_rect {
+        const CELL_SIZE: u32 = 15;
+        let start = self.opponent_field_start();
+        let width = self.game_size.width;
+        if self.opponent_cells_drawn.len() != self.opponent_cells.len() {
+            self.opponent_cells_drawn = vec![false; self.opponent_cells.len()];
+        }
+
+        for (i, filled) in self.opponent_cells.iter().enumerate() {
+            if *filled == self.opponent_cells_drawn[i] {
+                continue;
+            }
+            let pos = Point2 {
+                x: start.x + (i % width) as i32 * CELL_SIZE as i32,
+                y: start.y + (i / width) as i32 * CELL_SIZE as i32
+            };
+            if *filled {
+                canvas.fill_foreground(pos, Vector2 { x: CELL_SIZE, y: CELL_SIZE });
+            }else {
+                canvas.fill_background(pos, Vector2 { x: CELL_SIZE, y: CELL_SIZE });
+            }
+        }
+        self.opponent_cells_drawn = self.opponent_cells.clone();
+
+        mxcfb_rect {
+            left: start.x as u32,
+            top: start.y as u32,
+            width: width as u32 * CELL_SIZE,
+            height: self.game_size.height as u32 * CELL_SIZE,
+        }
+    }
+
+    fn queue_start(&self) -> Point2<i32> {
+        let field_start = self.field_start_i32();
+        Point2 { x: field_start.x - 50 - 4 * QUEUE_CELL_SIZE as i32, y: field_start.y }
+    }
+
+    /// Draws the upcoming pieces next to the playfield.
+    fn draw_queue(&mut self, canvas: &mut Canvas) -> mxcfb_rect {
+        let start = self.queue_start();
+        let slot_height = 3 * QUEUE_CELL_SIZE;
+        let rect = mxcfb_rect {
+            left: start.x as u32,
+            top: start.y as u32,
+            width: 4 * QUEUE_CELL_SIZE,
+            height: self.last_queue.len() as u32 * slot_height,
+        };
+        canvas.fill_background(
+            Point2 { x: rect.left as i32, y: rect.top as i32 },
+            Vector2 { x: rect.width, y: rect.height }
+        );
+        for (slot, kind) in self.last_queue.iter().enumerate() {
+            for (x, y) in Piece::new(*kind, 0, 0).cells() {
+                canvas.fill_foreground(
+                    Point2 {
+                        x: start.x + x * QUEUE_CELL_SIZE as i32,
+                        y: start.y + (slot as u32 * slot_height) as i32 + y * QUEUE_CELL_SIZE as i32
+                    },
+                    Vector2 { x: QUEUE_CELL_SIZE, y: QUEUE_CELL_SIZE }
+                );
+            }
+        }
+        rect
+    }
+
     /// Draws all blocks and returns a list of all rects that were changed
     /// and whether they are now filled or not.
     fn draw_blocks(&mut self, canvas: &mut Canvas) -> Vec<(mxcfb_rect, bool)> {
-        let mut blocks: HashMap<Point2<u8>, Block> = HashMap::new();
-        for block in self.game.draw() {
-            blocks.insert(Point2 { x: block.rect.origin.x as u8, y: block.rect.origin.y as u8 }, block);
+        let mut blocks: HashMap<Point2<u8>, Tile> = HashMap::new();
+        if self.ghost_piece && !self.game.is_game_over() {
+            for (x, y) in self.game.ghost().cells() {
+                if x >= 0 && y >= 0 {
+                    blocks.insert(Point2 { x: x as u8, y: y as u8 }, Tile::Ghost);
+                }
+            }
+        }
+        // Blocks cover the ghost
+        for block in self.game.blocks() {
+            blocks.insert(Point2 { x: block.x as u8, y: block.y as u8 }, Tile::Block(block.cell));
         }
 
         let mut changed_rects: Vec<(mxcfb_rect, bool)> = vec![];
@@ -205,32 +739,41 @@ impl GameScene {
         for y in 0..self.game_size().height {
             for x in 0..self.game_size().width {
                 let pos = Point2 { x: x as u8, y: y as u8 };
-                let was_filled = self.last_blocks.contains_key(&pos);
                 let is_filled = blocks.contains_key(&pos);
 
-                if was_filled != is_filled {
+                if self.last_blocks.get(&pos) != blocks.get(&pos) {
                     // Change detected
                     let block_start = self.to_coords((x, y));
                     let block_size = self.to_size((1,1));
 
-                    // Block went away
-                    if is_filled {
-                        let block = blocks.get(&pos).unwrap();
-                        let color = StupidColor::from(block.color.red, block.color.green, block.color.blue);
-                        if let Some(ref image) = self.textures.get(&color) {
-                            canvas.framebuffer_mut().draw_image(image, Point2 { x: block_start.0 as i32, y: block_start.1 as i32 });
+                    if let Some(Tile::Ghost) = blocks.get(&pos) {
+                        canvas.fill_background(
+                            Point2 { x: block_start.0 as i32, y: block_start.1 as i32 },
+                            Vector2 { x: block_size.0 as u32, y: block_size.1 as u32 }
+                        );
+                        canvas.draw_rect(
+                            Point2 { x: Some(block_start.0 as i32 + 5), y: Some(block_start.1 as i32 + 5) },
+                            Vector2 { x: block_size.0 as u32 - 10, y: block_size.1 as u32 - 10 },
+                            3
+                        );
+                    }else if is_filled {
+                        let texture = match blocks.get(&pos) {
+                            Some(Tile::Block(Cell::Piece(kind))) if self.block_patterns => self.textures.get(kind),
+                            _ => None,
+                        };
+                        if let Some(image) = texture {
+                            canvas.draw_image(image, Point2 { x: block_start.0 as i32, y: block_start.1 as i32 });
                         } else {
-                            canvas.framebuffer_mut().fill_rect(
+                            canvas.fill_foreground(
                                 Point2 { x: block_start.0 as i32, y: block_start.1 as i32 },
-                                Vector2 { x: block_size.0 as u32, y: block_size.1 as u32 },
-                                color::BLACK
+                                Vector2 { x: block_size.0 as u32, y: block_size.1 as u32 }
                             );
                         }
                     }else {
-                        canvas.framebuffer_mut().fill_rect(
+                        // Block went away
+                        canvas.fill_background(
                             Point2 { x: block_start.0 as i32, y: block_start.1 as i32 },
-                            Vector2 { x: block_size.0 as u32, y: block_size.1 as u32 },
-                            color::WHITE
+                            Vector2 { x: block_size.0 as u32, y: block_size.1 as u32 }
                         );
                     }
 
@@ -265,7 +808,7 @@ impl GameScene {
             x: field_size.x,
             y: FONT_SIZE + 30
         };
-        canvas.framebuffer_mut().fill_rect(pos, size, color::WHITE);
+        canvas.fill_background(pos, size);
         canvas.update_partial(&mxcfb_rect::from(Point2 { x: pos.x as u32, y: pos.y as u32 }, size));
 
         const FONT_SIZE: u32 = 40;
@@ -274,24 +817,240 @@ impl GameScene {
                 x: Some((field_start.x + 10) as i32),
                 y: Some((field_start.y + field_size.y + FONT_SIZE + 5) as i32)
             },
-            &format!("Score: {}", self.get_score()),
+            &if self.game.pending_garbage() > 0 {
+                format!("Score: {}   Garbage: {}", self.get_score(), self.game.pending_garbage())
+            }else {
+                format!("Score: {}", self.get_score())
+            },
             FONT_SIZE as f32,
         )
     }
+
+    /// Draws (or removes) the T-spin announcement left of the score.
+    fn draw_callout(&mut self, canvas: &mut Canvas) -> mxcfb_rect {
+        const FONT_SIZE: u32 = 40;
+        let field_start = self.field_start_u32();
+        let field_size = self.field_size();
+        let rect = mxcfb_rect {
+            left: 20,
+            top: field_start.y + field_size.y + 3,
+            width: field_start.x - 40,
+            height: FONT_SIZE + 30,
+        };
+        canvas.fill_background(
+            Point2 { x: rect.left as i32, y: rect.top as i32 },
+            Vector2 { x: rect.width, y: rect.height }
+        );
+        if let Some((ref text, _)) = self.callout {
+            canvas.draw_text(
+                Point2 { x: Some(50), y: Some((field_start.y + field_size.y + FONT_SIZE + 5) as i32) },
+                text,
+                FONT_SIZE as f32,
+            );
+        }
+        rect
+    }
+
+    /// Draws the "Pause"/"Resume", "Main Menu" and "Restart" buttons.
+    /// Returns the area of all of them.
+    fn draw_top_buttons(&mut self, canvas: &mut Canvas) -> mxcfb_rect {
+        self.play_pause_button.set_value(self.is_paused);
+        let can_restart = self.can_restart();
+        let mut buttons: Vec<&mut dyn Widget> = vec![&mut self.play_pause_button, &mut self.main_menu_button];
+        if can_restart {
+            buttons.push(&mut self.restart_button);
+        }
+        row(&mut buttons, Some(30), 28, 50);
+        draw_all(&mut buttons, canvas).unwrap()
+    }
+
+    /// Swaps the label of the pause button.
+    fn redraw_top_buttons(&mut self, canvas: &mut Canvas) {
+        let old_rect = self.play_pause_button.rect().merge_rect(&self.main_menu_button.rect());
+        let old_rect = if self.restart_button.is_shown() {
+            old_rect.merge_rect(&self.restart_button.rect())
+        }else {
+            old_rect
+        };
+        canvas.fill_background(
+            Point2 { x: old_rect.left as i32, y: old_rect.top as i32 },
+            old_rect.size()
+        );
+        if self.control_scheme == ControlScheme::TapZones && self.tap_zones.show_outlines {
+            self.draw_tap_zones(canvas);
+        }
+        let new_rect = self.draw_top_buttons(canvas);
+        canvas.update_partial(&old_rect.merge_rect(&new_rect));
+    }
+
+    /// Covers the playfield and the next pieces so that
+    /// pausing can't be used to plan ahead.
+    fn draw_pause_overlay(&mut self, canvas: &mut Canvas) {
+        let field_start = self.field_start_i32();
+        let field_size = self.field_size();
+        canvas.fill_background(field_start, field_size);
+
+        let queue_start = self.queue_start();
+        let queue_size = Vector2 { x: 4 * QUEUE_CELL_SIZE, y: self.last_queue.len() as u32 * 3 * QUEUE_CELL_SIZE };
+        canvas.fill_background(queue_start, queue_size);
+        canvas.update_partial(&mxcfb_rect::from(Point2 { x: queue_start.x as u32, y: queue_start.y as u32 }, queue_size));
+        // Gets drawn again when resuming
+        self.last_queue.clear();
+
+        canvas.draw_text(Point2 { x: None, y: Some(field_start.y + 200) }, "Paused", 100.0);
+        let can_restart = self.can_restart();
+        for button in self.pause_menu.buttons_mut() {
+            if button.action() == HudAction::Restart {
+                button.set_enabled(can_restart);
+            }
+        }
+        column(&mut [&mut self.pause_menu], None, field_start.y + 335, 0);
+        self.pause_menu.draw(canvas);
+
+        // A fast refresh would leave traces of the blocks
+        canvas.update_partial_clean(&mxcfb_rect::from(self.field_start_u32(), field_size));
+        self.redraw_top_buttons(canvas);
+    }
+
+    /// Brings back the playfield with a single refresh.
+    fn remove_pause_overlay(&mut self, canvas: &mut Canvas) {
+        self.pause_menu.hide();
+
+        self.redraw_field(canvas);
+        self.redraw_top_buttons(canvas);
+    }
+
+    /// Draws the whole playfield again with a single refresh.
+    fn redraw_field(&mut self, canvas: &mut Canvas) {
+        let field_size = self.field_size();
+        canvas.fill_background(self.field_start_i32(), field_size);
+        self.last_blocks.clear();
+        self.draw_blocks(canvas);
+        canvas.update_partial_clean(&mxcfb_rect::from(self.field_start_u32(), field_size));
+    }
+}
+
+fn describe_t_spin(clear: TSpinClear) -> String {
+    let name = match clear.t_spin {
+        TSpin::Mini => "Mini T-Spin",
+        TSpin::Full => "T-Spin",
+    };
+    match clear.lines {
+        0 => name.to_owned(),
+        1 => format!("{} Single!", name),
+        2 => format!("{} Double!", name),
+        _ => format!("{} Triple!", name),
+    }
 }
 
 impl Scene for GameScene {
+    fn next_tick(&self) -> Option<Duration> {
+        if self.last_draw.is_none() {
+            return Some(Duration::ZERO);
+        }
+        if self.versus.is_some() {
+            // The opponent can send something at any time
+            return Some(FRAME_DURATION);
+        }
+        let now = Instant::now();
+        let mut ticks: Vec<Duration> = vec![self.status_bar.next_tick()];
+        if let Some((_, since)) = self.callout {
+            ticks.push((since + CALLOUT_DURATION).saturating_duration_since(now));
+        }
+        if !self.is_paused {
+            if let Some(remaining) = self.game.next_update_in() {
+                ticks.push(Duration::from_secs_f64(remaining));
+            }
+            if let Some(next_repeat) = self.hold_tracker.next_repeat() {
+                ticks.push(next_repeat.saturating_duration_since(now));
+            }
+        }
+        ticks.into_iter().min()
+    }
+
+    fn on_suspend(&mut self) {
+        self.set_paused(true);
+    }
+
+    fn state_text(&self) -> Option<String> {
+        // As puzzle level, so it can be played again
+        let mut pieces = vec![self.game.active().kind];
+        pieces.extend(self.game.queue().take(self.visible_queue_len()));
+        let name = match self.puzzle {
+            Some(ref level) => format!("{} (saved)", level.name),
+            None => "Saved game".to_owned(),
+        };
+        let goal = self.puzzle.as_ref().map(|level| level.goal).unwrap_or(Goal::Lines(1));
+        Some(puzzle::level_text(&name, goal, &pieces, self.game.board()))
+    }
+
+    fn invalidate(&mut self) {
+        // Draw everything like on the first frame
+        self.last_draw = None;
+        self.last_queue.clear();
+        self.opponent_cells_drawn.clear();
+        self.callout_changed = true;
+        // Releases might have been missed
+        self.restart_tap_tracker.reset();
+        self.last_pressed_finger = None;
+        if self.is_paused {
+            self.overlay_changed = true;
+        }else {
+            self.field_changed = true;
+        }
+    }
+
     fn on_input(&mut self, event: InputEvent) {
-        match event {
-            InputEvent::GPIO { event } => {
-                if let GPIOEvent::Press { button } = event {
-                    match button {
-                        PhysicalButton::MIDDLE => self.game.perform(Action::Rotate),
-                        PhysicalButton::LEFT => self.game.perform(Action::MoveLeft),
-                        PhysicalButton::RIGHT => self.game.perform(Action::MoveRight),
-                        PhysicalButton::POWER => self.game.perform(Action::MoveDown),
-                        _ => { }
+        if let InputEvent::MultitouchEvent { event } = event {
+            // Gesture: Tap with three fingers (not while the pause overlay is open)
+            if self.restart_tap_tracker.detect(event) && !self.is_paused {
+                self.restart();
+                return;
+            }
+        }
+        if let InputEvent::MultitouchEvent { event } = event {
+            if let Some(action) = self.on_touch(&event) {
+                // Released on one of the buttons
+                self.on_hud_action(action);
+                return;
+            }
+        }
+        if self.is_paused {
+            // Only the buttons work (hardware ones go through the menu)
+            match navigation(&event) {
+                Some(Navigation::Move(step)) => move_focus(&mut self.pause_menu.focusables(), step),
+                Some(Navigation::Activate) => {
+                    if let Some(action) = self.pause_menu.activate() {
+                        self.on_hud_action(action);
                     }
+                },
+                None => {},
+            }
+            return;
+        }
+        match event {
+            InputEvent::GPIO { event: GPIOEvent::Press { button } } => {
+                match button {
+                    PhysicalButton::MIDDLE => {
+                        self.game.perform(Action::RotateCw);
+                    },
+                    PhysicalButton::LEFT => {
+                        let event = self.hold_tracker.press(HoldSource::Button(button), Shift::Left, Instant::now());
+                        self.on_hold_event(event);
+                    },
+                    PhysicalButton::RIGHT => {
+                        let event = self.hold_tracker.press(HoldSource::Button(button), Shift::Right, Instant::now());
+                        self.on_hold_event(event);
+                    },
+                    PhysicalButton::POWER => {
+                        self.game.perform(Action::MoveDown);
+                    },
+                    _ => { }
+                }
+            },
+            InputEvent::GPIO { event: GPIOEvent::Unpress { button } } => {
+                if let Some(event) = self.hold_tracker.release(HoldSource::Button(button)) {
+                    self.on_hold_event(event);
                 }
             },
             InputEvent::MultitouchEvent { event } => {
@@ -300,36 +1059,48 @@ impl Scene for GameScene {
                 match event {
                     MultitouchEvent::Press { finger } => {
                         self.last_pressed_finger = Some((finger, Instant::now()));
-                        // This finger can only control the current block with swipes
-                        self.finger_controls_which_block.insert(finger.tracking_id, self.block_id.load(Ordering::Relaxed));
+                        if let Some(shift) = self.arrow_button_at(finger.pos) {
+                            // Button: « or » (moves as long as held)
+                            let event = self.hold_tracker.press(HoldSource::Finger(finger.tracking_id), shift, Instant::now());
+                            self.on_hold_event(event);
+                        }else {
+                            // This finger can only control the current block with swipes
+                            self.finger_controls_which_block.insert(finger.tracking_id, self.block_id.load(Ordering::Relaxed));
+                            let field = mxcfb_rect::from(self.field_start_u32(), self.field_size());
+                            if self.control_scheme == ControlScheme::Drag && Canvas::is_hitting(finger.pos, field) {
+                                self.drag_tracker.start(finger.tracking_id, finger.pos);
+                            }
+                        }
+                    },
+                    MultitouchEvent::Move { finger } => {
+                        if let Some(x) = self.drag_tracker.detect(event) {
+                            self.drag(finger.tracking_id, x);
+                        }
+
+                        let source = HoldSource::Finger(finger.tracking_id);
+                        if self.hold_tracker.is_holding(source) && self.arrow_button_at(finger.pos).is_none() {
+                            // Slid off the arrow button
+                            if let Some(event) = self.hold_tracker.release(source) {
+                                self.on_hold_event(event);
+                            }
+                        }
                     },
                     MultitouchEvent::Release { finger: up_finger } => {
-                        if let Some((down_finger, down_when)) = self.last_pressed_finger {
-                            if down_finger.tracking_id == up_finger.tracking_id
-                               && down_when.elapsed().as_millis() < 300 {
-                                let x_dist = up_finger.pos.x as i32 - down_finger.pos.x as i32;
-                                let y_dist = up_finger.pos.y as i32 - down_finger.pos.y as i32;
-                                let dist = (x_dist.pow(2) as f32 + y_dist.pow(2) as f32).sqrt();
-                                if dist < 20.0 {
-                                    // Short tap recognized
-
-                                    if self.play_pause_button_hitbox.is_some() && Canvas::is_hitting(up_finger.pos, self.play_pause_button_hitbox.unwrap()) {
-                                        // Button: Pause
-                                        self.is_paused = ! self.is_paused;
-                                    } else if self.back_button_hitbox.is_some() && Canvas::is_hitting(up_finger.pos, self.back_button_hitbox.unwrap()) {
-                                        // Button: Main Menu
-                                        self.back_button_pressed = true;
-                                    } else if self.left_button_hitbox.is_some() && Canvas::is_hitting(up_finger.pos, self.left_button_hitbox.unwrap()) {
-                                        // Button: «
-                                        self.game.perform(Action::MoveLeft);
-                                    } else if self.right_button_hitbox.is_some() && Canvas::is_hitting(up_finger.pos, self.right_button_hitbox.unwrap()) {
-                                        // Button: »
-                                        self.game.perform(Action::MoveRight);
-                                    }else {
-                                        // Somewhere else
-                                        self.game.perform(Action::Rotate);
-                                    }
-                                }
+                        self.drag_tracker.detect(event);
+                        if let Some(event) = self.hold_tracker.release(HoldSource::Finger(up_finger.tracking_id)) {
+                            self.on_hold_event(event);
+                        }
+                        if self.is_tap(&up_finger) {
+                            // Short tap recognized
+
+                            if self.arrow_button_at(up_finger.pos).is_some() {
+                                // Button: « or » (already moved when pressed)
+                            }else if self.control_scheme == ControlScheme::TapZones {
+                                // Somewhere else (tap zone)
+                                self.on_tap_zone(self.tap_zones.zone_at(up_finger.pos));
+                            }else {
+                                // Somewhere else
+                                self.game.perform(Action::RotateCw);
                             }
                         }
                     },
@@ -337,26 +1108,38 @@ impl Scene for GameScene {
                 }
 
                 // Movement (swipes)
-                const SWIPES: [Swipe; 3] = [
-                    Swipe { direction: Direction::Down, trigger: Trigger::Completed },
+                const SWIPES: [Swipe; 6] = [
+                    Swipe { direction: Direction::Down, trigger: Trigger::Flick { min_velocity: HARD_DROP_MIN_VELOCITY } },
+                    Swipe { direction: Direction::Down, trigger: Trigger::MinDistance(50) },
                     Swipe { direction: Direction::Left, trigger: Trigger::MinDistance(50) },
                     Swipe { direction: Direction::Right, trigger: Trigger::MinDistance(50) },
+                    Swipe { direction: Direction::Up, trigger: Trigger::Flick { min_velocity: HALF_ROTATION_MIN_VELOCITY } },
+                    Swipe { direction: Direction::Up, trigger: Trigger::Completed },
+                ];
+                // Left and right are done by dragging instead
+                const DRAG_SWIPES: [Swipe; 4] = [
+                    Swipe { direction: Direction::Down, trigger: Trigger::Flick { min_velocity: HARD_DROP_MIN_VELOCITY } },
+                    Swipe { direction: Direction::Down, trigger: Trigger::MinDistance(50) },
+                    Swipe { direction: Direction::Up, trigger: Trigger::Flick { min_velocity: HALF_ROTATION_MIN_VELOCITY } },
+                    Swipe { direction: Direction::Up, trigger: Trigger::Completed },
                 ];
+                let swipes: &[Swipe] = match self.control_scheme {
+                    ControlScheme::Swipe | ControlScheme::TapZones => &SWIPES,
+                    ControlScheme::Drag => &DRAG_SWIPES,
+                };
 
-                let tracking_id = &event.finger().unwrap().tracking_id;
-                if let Some(swipe) = self.swipe_tracker.detect(event, &SWIPES) {
+                let tracking_id = event.finger().unwrap().tracking_id;
+                if let Some(swipe) = self.swipe_tracker.detect(event, swipes) {
                     if self.finger_controls_which_block.get(&tracking_id) == Some(&self.block_id.load(Ordering::Relaxed)) { // Is current?
-                        match swipe.direction {
-                            Direction::Left => self.game.perform(Action::MoveLeft),
-                            Direction::Right => self.game.perform(Action::MoveRight),
-                            Direction::Down => {
-                                // Push all the way down
-                                for _ in 0..self.game_size.height {
-                                    self.game.perform(Action::MoveDown);
-                                }
-                            }
-                            Direction::Up => self.game.perform(Action::Rotate),
-                        }
+                        let action = match (swipe.direction, swipe.trigger) {
+                            (Direction::Left, _) => Action::MoveLeft,
+                            (Direction::Right, _) => Action::MoveRight,
+                            (Direction::Down, Trigger::Flick { .. }) => Action::HardDrop,
+                            (Direction::Down, _) => Action::MoveDown,
+                            (Direction::Up, Trigger::Flick { .. }) => Action::Rotate180,
+                            (Direction::Up, _) => Action::RotateCcw,
+                        };
+                        self.game.perform(action);
                     }
                 }
 
@@ -372,84 +1155,175 @@ impl Scene for GameScene {
         if let Some(last_draw) = self.last_draw {
             // Advance physics
             if ! self.is_paused {
-                self.game.update(last_draw.elapsed().as_secs_f64() * self.speed);
+                self.game.update(last_draw.elapsed().as_secs_f64());
+                for event in self.hold_tracker.poll(Instant::now()) {
+                    self.on_hold_event(event);
+                }
             }
         }else {
             // First frame
             canvas.clear();
+            if self.control_scheme == ControlScheme::TapZones && self.tap_zones.show_outlines {
+                self.draw_tap_zones(canvas);
+            }
             canvas.draw_text(Point2 { x: None, y: Some(self.field_start_i32().y - 50)}, "reTris", 200.0);
 
             let point = Point2 { x: self.field_start_i32().x - 2, y: self.field_start_i32().y - 2 };
             let vec = Vector2 { x: 2 + self.field_size().x + 2, y: 2 + self.field_size().y + 2 };
-            canvas.framebuffer_mut().draw_rect(point, vec, 1, color::BLACK);
-
-            self.play_pause_button_hitbox = Some(canvas.draw_button(Point2 { x: Some(50), y: Some(75) }, "Pause", 50.0, 10, 20));
-            self.back_button_hitbox = Some(canvas.draw_button(Point2 {
-                x: Some(self.play_pause_button_hitbox.unwrap().left as i32 + self.play_pause_button_hitbox.unwrap().width as i32 + 50),
-                y: Some(75)
-            }, "Main Menu", 50.0, 10, 20));
-
-            let lr_y_pos = 1780;
-            let lr_x_margin = 75;
-            let lr_vgap = 50;
-            let lr_hgap = 50;
-            let lr_font_size = 100.0;
-            if ! crate::CLI_OPTS.no_arrow_buttons {
-                self.left_button_hitbox = Some(canvas.draw_button(Point2 { x: Some(lr_x_margin), y: Some(lr_y_pos) }, "«", lr_font_size, lr_vgap, lr_hgap));
-                self.right_button_hitbox = Some(canvas.draw_button(Point2 {
-                    x: Some(DISPLAYWIDTH as i32 + lr_hgap as i32 - (self.left_button_hitbox.unwrap().left as i32 + self.left_button_hitbox.unwrap().width as i32)),
-                    y: Some(lr_y_pos)
-                }, "»", lr_font_size, lr_vgap, lr_hgap));
+            canvas.draw_rect(Point2 { x: Some(point.x), y: Some(point.y) }, vec, 1);
+
+            self.draw_top_buttons(canvas);
+
+            // Arrow buttons in the corners at the bottom
+            self.left_button.hide();
+            self.right_button.hide();
+            if self.arrow_buttons {
+                let top = DISPLAYHEIGHT as i32 - 40 - self.left_button.size().y as i32;
+                let right_x = DISPLAYWIDTH as i32 - 25 - self.right_button.size().x as i32;
+                self.left_button.set_pos(Point2 { x: 25, y: top });
+                self.right_button.set_pos(Point2 { x: right_x, y: top });
+                draw_all(&mut [&mut self.left_button, &mut self.right_button], canvas);
             }
 
+            let queue_start = self.queue_start();
+            canvas.draw_text(Point2 { x: Some(queue_start.x), y: Some(queue_start.y - 15) }, "Next", 30.0);
+
+            if let Some(ref level) = self.puzzle {
+                // Left of the playfield below the queue
+                let goal_top = queue_start.y + 3 * 3 * QUEUE_CELL_SIZE as i32 + 65;
+                let width = (self.field_start_i32().x - 50 - 30) as u32;
+                let name_rect = canvas.draw_text_box(
+                    &TextBox::new(mxcfb_rect { left: 30, top: goal_top as u32, width, height: 0 }, 35.0),
+                    &level.name
+                );
+                canvas.draw_text_box(
+                    &TextBox::new(mxcfb_rect { left: 30, top: name_rect.top + name_rect.height + 15, width, height: 0 }, 30.0),
+                    &format!("Goal: {}", level.goal)
+                );
+            }
+
+            if self.versus.is_some() {
+                let opponent_start = self.opponent_field_start();
+                canvas.draw_text(Point2 { x: Some(opponent_start.x), y: Some(opponent_start.y - 15) }, "Opponent", 30.0);
+                canvas.draw_rect(
+                    Point2 { x: Some(opponent_start.x - 2), y: Some(opponent_start.y - 2) },
+                    Vector2 { x: 2 + self.game_size.width as u32 * 15 + 2, y: 2 + self.game_size.height as u32 * 15 + 2 },
+                    1
+                );
+                self.draw_opponent(canvas);
+            }
+
+            self.status_bar.invalidate();
+            self.status_bar.draw(canvas);
+
             canvas.update_full();
             self.draw_score(canvas);
         }
         self.last_draw = Some(Instant::now());
 
+        if self.overlay_changed {
+            self.overlay_changed = false;
+            if self.is_paused {
+                self.draw_pause_overlay(canvas);
+            }else {
+                self.remove_pause_overlay(canvas);
+            }
+        }
+        if self.field_changed {
+            self.field_changed = false;
+            self.redraw_field(canvas);
+        }
+
+        // Show right away that a button is pressed
+        self.play_pause_button.draw_feedback(canvas);
+        self.main_menu_button.draw_feedback(canvas);
+        self.restart_button.draw_feedback(canvas);
+        self.left_button.draw_feedback(canvas);
+        self.right_button.draw_feedback(canvas);
+        self.pause_menu.draw_feedback(canvas);
+
+        // The board stays hidden while paused
+        let block_changes = if self.is_paused { vec![] } else { self.draw_blocks(canvas) };
+
+        if self.update_versus() {
+            let rect = self.draw_opponent(canvas);
+            canvas.update_partial_mono(&rect);
+        }
+
+        let queue: Vec<PieceKind> = self.game.queue().copied().take(self.visible_queue_len()).collect();
+        if queue != self.last_queue && !self.is_paused {
+            self.last_queue = queue;
+            let rect = self.draw_queue(canvas);
+            canvas.update_partial_mono(&rect);
+        }
+
+        if let Some(clear) = self.game.take_t_spin() {
+            self.callout = Some((describe_t_spin(clear), Instant::now()));
+            self.callout_changed = true;
+            if clear.t_spin == TSpin::Full && clear.lines > 0 {
+                self.puzzle_t_spins += 1;
+            }
+            if clear.t_spin == TSpin::Full && clear.lines >= 2 {
+                self.puzzle_t_spin_doubles += 1;
+            }
+        }
+        self.update_puzzle();
+        if let Some((_, since)) = self.callout {
+            if since.elapsed() > CALLOUT_DURATION {
+                self.callout = None;
+                self.callout_changed = true;
+            }
+        }
+        if self.callout_changed {
+            self.callout_changed = false;
+            let rect = self.draw_callout(canvas);
+            canvas.update_partial(&rect);
+        }
+
         // Update score if changed
-        if self.last_score != self.get_score() {
+        if self.last_score != self.get_score() || self.last_pending_garbage != self.game.pending_garbage() {
             self.last_score = self.get_score();
+            self.last_pending_garbage = self.game.pending_garbage();
             let rect = self.draw_score(canvas);
             canvas.update_partial(&rect);
         }
 
-        let block_changes = self.draw_blocks(canvas);
-        if block_changes.len() > 0 {
+        if let Some(rect) = self.status_bar.draw(canvas) {
+            canvas.update_partial(&rect);
+        }
+
+        if !block_changes.is_empty() {
             // Not sure if doing seperate transitions is a good or bad thing on either
             // rM1 or rM2. On the rM1 it seems to reduce some artifacts.
             //
             // Waiting for refreshes on the rM2 is currently stubbed by rm2fb. On the rM1
             // it takes about 350ms for each, making the game lag when waiting.
 
-            match CURRENT_DEVICE.model {
-                Model::Gen1 => {
-                    // Do all white -> black transitions first (they are faster)
-                    block_changes
-                        .iter()
-                        .filter(|(_, filled)| *filled)
-                        .for_each(|(rect, _)| canvas.update_partial(rect));
-
-                    // Do all black -> white transitions (they take longer anyway)
-                    block_changes
-                        .iter()
-                        .filter(|(_, filled)| !*filled)
-                        .for_each(|(rect, _)| canvas.update_partial(rect));
-                },
-                Model::Gen2 => {
-                    // Do all white -> black transitions first (they are faster)
-                    block_changes
-                        .iter()
-                        .filter(|(_, filled)| *filled)
-                        .for_each(|(rect, _)| canvas.update_partial_mono(rect));
-
-                    // Do all black -> white transitions (they take longer anyway)
-                    block_changes
-                        .iter()
-                        .filter(|(_, filled)| !*filled)
-                        .for_each(|(rect, _)| canvas.update_partial_mono(rect));
+            let mono = match self.refresh_policy {
+                RefreshPolicy::Auto => match CURRENT_DEVICE.model {
+                    Model::Gen1 => false,
+                    Model::Gen2 => true,
                 },
-            }
+                RefreshPolicy::Fast => true,
+                RefreshPolicy::Quality => false,
+            };
+            let mut refresh = |rect: &mxcfb_rect| if mono {
+                canvas.update_partial_mono(rect)
+            }else {
+                canvas.update_partial(rect)
+            };
+
+            // Do all white -> black transitions first (they are faster)
+            block_changes
+                .iter()
+                .filter(|(_, filled)| *filled)
+                .for_each(|(rect, _)| refresh(rect));
+
+            // Do all black -> white transitions (they take longer anyway)
+            block_changes
+                .iter()
+                .filter(|(_, filled)| !*filled)
+                .for_each(|(rect, _)| refresh(rect));
         }
     }
 }
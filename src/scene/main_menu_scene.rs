@@ -1,102 +1,179 @@
 use super::Scene;
 use crate::canvas::*;
+use crate::config::Config;
+use crate::status_bar::StatusBar;
+use crate::versus::VersusOutcome;
+use crate::widget::*;
 use libremarkable::input::{InputEvent, MultitouchEvent};
+use std::time::Duration;
+
+/// What the player chose. Taken by main to switch scenes.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum MainMenuAction {
+    PlayEasy,
+    PlayNormal,
+    PlayHard,
+    Versus,
+    Puzzles,
+    Calibrate,
+    Settings,
+    Exit,
+    ExitXochitl,
+}
 
 pub struct MainMenuScene {
     drawn: bool,
     
-    play_easy_button_hitbox: Option<mxcfb_rect>,
-    pub play_easy_button_pressed: bool,
-    play_normal_button_hitbox: Option<mxcfb_rect>,
-    pub play_normal_button_pressed: bool,
-    play_hard_button_hitbox: Option<mxcfb_rect>,
-    pub play_hard_button_pressed: bool,
-
-    exit_button_hitbox: Option<mxcfb_rect>,
-    pub exit_button_pressed: bool,
-
-    exit_xochitl_button_hitbox: Option<mxcfb_rect>,
-    pub exit_xochitl_button_pressed: bool,
+    title: Label,
+    calibrate_button: Button<MainMenuAction>,
+    settings_button: Button<MainMenuAction>,
+    difficulties: List<MainMenuAction>,
+    versus_button: Button<MainMenuAction>,
+    puzzles_button: Button<MainMenuAction>,
+    exit_button: Button<MainMenuAction>,
+    pub action: Option<MainMenuAction>,
 
     score: Option<u64>,
-
-    only_exit_to_xochitl: bool,
+    versus_outcome: Option<VersusOutcome>,
+    status_bar: StatusBar,
 }
 
 impl MainMenuScene {
     pub fn new(score: Option<u64>, only_exit_to_xochitl: bool) -> Self {
+        let exit_button = if only_exit_to_xochitl {
+            Button::new("Exit to Xochitl", 125.0, MainMenuAction::ExitXochitl)
+        }else {
+            Button::new("Exit", 125.0, MainMenuAction::Exit)
+        };
         Self {
             drawn: false,
-            play_easy_button_hitbox: None,
-            play_easy_button_pressed: false,
-            play_normal_button_hitbox: None,
-            play_normal_button_pressed: false,
-            play_hard_button_hitbox: None,
-            play_hard_button_pressed: false,
-            exit_button_hitbox: None,
-            exit_button_pressed: false,
-            exit_xochitl_button_hitbox: None,
-            exit_xochitl_button_pressed: false,
+            title: Label::new("reTris", 400.0),
+            calibrate_button: Button::new("Calibrate", 50.0, MainMenuAction::Calibrate).with_gaps(10, 20),
+            settings_button: Button::new("Settings", 50.0, MainMenuAction::Settings).with_gaps(10, 20),
+            difficulties: List::new(30)
+                .with_button(Button::new("Easy", 125.0, MainMenuAction::PlayEasy).with_gaps(15, 50))
+                .with_button(Button::new("Normal", 125.0, MainMenuAction::PlayNormal).with_gaps(15, 50))
+                .with_button(Button::new("Hard", 125.0, MainMenuAction::PlayHard).with_gaps(15, 50)),
+            versus_button: Button::new("Versus", 125.0, MainMenuAction::Versus).with_gaps(15, 50),
+            puzzles_button: Button::new("Puzzles", 125.0, MainMenuAction::Puzzles).with_gaps(15, 50),
+            exit_button: exit_button.with_gaps(15, 50),
+            action: None,
             score,
-            only_exit_to_xochitl,
+            versus_outcome: None,
+            status_bar: StatusBar::from_config(&Config::current()),
         }
     }
+
+    pub fn with_versus_outcome(mut self, versus_outcome: Option<VersusOutcome>) -> Self {
+        self.versus_outcome = versus_outcome;
+        self
+    }
+
+    /// Every button needs to see all touch events to follow presses.
+    fn on_touch(&mut self, event: &MultitouchEvent) -> Option<MainMenuAction> {
+        [
+            self.calibrate_button.on_touch(event),
+            self.settings_button.on_touch(event),
+            self.difficulties.on_touch(event),
+            self.versus_button.on_touch(event),
+            self.puzzles_button.on_touch(event),
+            self.exit_button.on_touch(event),
+        ].iter().find_map(|action| *action)
+    }
+
+    /// In the order the hardware buttons go through them.
+    fn focusables(&mut self) -> Vec<&mut dyn Focusable> {
+        let mut focusables: Vec<&mut dyn Focusable> = vec![&mut self.calibrate_button, &mut self.settings_button];
+        focusables.append(&mut self.difficulties.focusables());
+        focusables.push(&mut self.versus_button);
+        focusables.push(&mut self.puzzles_button);
+        focusables.push(&mut self.exit_button);
+        focusables
+    }
+
+    fn activate(&self) -> Option<MainMenuAction> {
+        [
+            self.calibrate_button.activate(),
+            self.settings_button.activate(),
+            self.difficulties.activate(),
+            self.versus_button.activate(),
+            self.puzzles_button.activate(),
+            self.exit_button.activate(),
+        ].iter().find_map(|action| *action)
+    }
 }
 
 impl Scene for MainMenuScene {
     fn draw(&mut self, canvas: &mut Canvas) {
         if self.drawn {
+            self.calibrate_button.draw_feedback(canvas);
+            self.settings_button.draw_feedback(canvas);
+            self.difficulties.draw_feedback(canvas);
+            self.versus_button.draw_feedback(canvas);
+            self.puzzles_button.draw_feedback(canvas);
+            self.exit_button.draw_feedback(canvas);
+            if let Some(rect) = self.status_bar.draw(canvas) {
+                canvas.update_partial(&rect);
+            }
             return;
         }
         self.drawn = true;
 
         canvas.clear();
-        canvas.draw_text(Point2 { x: None, y: Some(500)}, "reTris", 400.0);
+        column(&mut [&mut self.title], None, 130, 0);
+        row(&mut [&mut self.calibrate_button, &mut self.settings_button], Some(30), 28, 50);
+        draw_all(&mut [&mut self.title, &mut self.calibrate_button, &mut self.settings_button], canvas);
 
         if let Some(score) = self.score {
-               canvas.draw_text(Point2 { x: None, y: Some(700)}, "Game Over!", 75.0);
-               canvas.draw_text(Point2 { x: None, y: Some(775)}, &format!("Score: {}", score), 75.0);
+               let headline = match self.versus_outcome {
+                   Some(VersusOutcome::Won) => "You won!",
+                   Some(VersusOutcome::Lost) => "You lost!",
+                   Some(VersusOutcome::OpponentLeft) => "Opponent left!",
+                   None => "Game Over!",
+               };
+               let mut headline = Label::new(headline, 75.0);
+               let mut score = Label::new(&format!("Score: {}", score), 75.0);
+               column(&mut [&mut headline, &mut score], None, 620, 0);
+               draw_all(&mut [&mut headline, &mut score], canvas);
         }
         
-        self.play_easy_button_hitbox = Some(canvas.draw_button(Point2 { x: None, y: Some(1000) }, "Easy", 125.0, 25, 50));
-        self.play_normal_button_hitbox = Some(canvas.draw_button(Point2 {
-            x: None,
-            y: Some(150 + self.play_easy_button_hitbox.unwrap().top as i32 + self.play_easy_button_hitbox.unwrap().height as i32)
-        }, "Normal", 125.0, 25, 50));
-        self.play_hard_button_hitbox = Some(canvas.draw_button(Point2 {
-            x: None,
-            y: Some(150 + self.play_normal_button_hitbox.unwrap().top as i32 + self.play_normal_button_hitbox.unwrap().height as i32)
-        }, "Hard", 125.0, 25, 50));
-        
-        if self.only_exit_to_xochitl { 
-            self.exit_xochitl_button_hitbox = Some(canvas.draw_button(Point2 { x: None, y: Some(1650) }, "Exit to Xochitl", 125.0, 25, 50));
-        }else {
-            self.exit_button_hitbox = Some(canvas.draw_button(Point2 { x: None, y: Some(1650) }, "Exit", 125.0, 25, 50));
-        }
+        column(&mut [&mut self.difficulties], None, 820, 0);
+        let modes_y = self.difficulties.size().y as i32 + 820 + 30;
+        // Both in one row
+        row(&mut [&mut self.versus_button, &mut self.puzzles_button], None, modes_y, 100);
+        let exit_y = DISPLAYHEIGHT as i32 - 40 - self.exit_button.size().y as i32;
+        column(&mut [&mut self.exit_button], None, exit_y, 0);
+        draw_all(&mut [&mut self.difficulties, &mut self.versus_button, &mut self.puzzles_button, &mut self.exit_button], canvas);
+
+        self.status_bar.invalidate();
+        self.status_bar.draw(canvas);
         
         canvas.update_full();
     }
 
+    fn next_tick(&self) -> Option<Duration> {
+        if self.drawn { Some(self.status_bar.next_tick()) } else { Some(Duration::ZERO) }
+    }
+
+    fn invalidate(&mut self) {
+        self.drawn = false;
+    }
+
     fn on_input(&mut self, event: InputEvent) {
-        if let InputEvent::MultitouchEvent { event } = event {
-            if let MultitouchEvent::Press { finger, .. } = event {
-                let position = finger.pos;
-                if self.play_easy_button_hitbox.is_some() && Canvas::is_hitting(position, self.play_easy_button_hitbox.unwrap()) {
-                    self.play_easy_button_pressed = true;
-                }
-                if self.play_normal_button_hitbox.is_some() && Canvas::is_hitting(position, self.play_normal_button_hitbox.unwrap()) {
-                    self.play_normal_button_pressed = true;
-                }
-                if self.play_hard_button_hitbox.is_some() && Canvas::is_hitting(position, self.play_hard_button_hitbox.unwrap()) {
-                    self.play_hard_button_pressed = true;
-                }
-                if self.exit_button_hitbox.is_some() && Canvas::is_hitting(position, self.exit_button_hitbox.unwrap()) {
-                    self.exit_button_pressed = true;
-                }
-                if self.exit_xochitl_button_hitbox.is_some() && Canvas::is_hitting(position, self.exit_xochitl_button_hitbox.unwrap()) {
-                    self.exit_xochitl_button_pressed = true;
-                }
-            }
+        let action = match event {
+            // Buttons trigger when released
+            InputEvent::MultitouchEvent { event } => self.on_touch(&event),
+            _ => match navigation(&event) {
+                Some(Navigation::Move(step)) => {
+                    move_focus(&mut self.focusables(), step);
+                    None
+                },
+                Some(Navigation::Activate) => self.activate(),
+                None => None,
+            },
+        };
+        if action.is_some() {
+            self.action = action;
         }
     }
-}
\ No newline at end of file
+}
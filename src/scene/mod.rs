@@ -1,15 +1,45 @@
 mod game_scene;
 mod main_menu_scene;
+mod puzzle_select_scene;
+mod settings_scene;
+mod swipe_calibration_scene;
+mod versus_lobby_scene;
 
 pub use game_scene::GameScene;
-pub use main_menu_scene::MainMenuScene;
+pub use main_menu_scene::{MainMenuAction, MainMenuScene};
+pub use puzzle_select_scene::PuzzleSelectScene;
+pub use settings_scene::SettingsScene;
+pub use swipe_calibration_scene::SwipeCalibrationScene;
+pub use versus_lobby_scene::VersusLobbyScene;
 
 use crate::canvas::Canvas;
+use crate::config::Config;
 use downcast_rs::Downcast;
 use libremarkable::input::InputEvent;
+use std::time::Duration;
+
+/// Shortest time between two frames. The display can't keep up with more anyway.
+pub const FRAME_DURATION: Duration = Duration::from_millis(1000 / 30);
 
 pub trait Scene: Downcast {
     fn on_input(&mut self, _event: InputEvent) { }
     fn draw(&mut self, canvas: &mut Canvas);
+    /// Time until draw() needs to be called again even without
+    /// any input. None if only input can change anything.
+    fn next_tick(&self) -> Option<Duration> { None }
+    /// The app is about to go into the background.
+    fn on_suspend(&mut self) { }
+    /// Everything has to be drawn again on the next draw()
+    /// since another app used the screen in between.
+    fn invalidate(&mut self);
+    /// The state as text (e.g. the board), saved next to screenshots.
+    fn state_text(&self) -> Option<String> { None }
 }
 impl_downcast!(Scene);
+
+/// Saves the settings changed in a scene and reports failing.
+fn save_config(config: &Config) {
+    if let Err(err) = config.save() {
+        eprintln!("Failed to save config to {:?}: {}", Config::path(), err);
+    }
+}
@@ -0,0 +1,224 @@
+use super::Scene;
+use crate::canvas::*;
+use crate::engine::Size;
+use crate::puzzle::{self, Level, PuzzleOutcome};
+use crate::widget::*;
+use libremarkable::input::{InputEvent, MultitouchEvent};
+use std::time::Duration;
+
+const LEVELS_PER_PAGE: usize = 8;
+const LEVEL_Y_START: i32 = 590;
+const LEVEL_GAP: u32 = 40;
+
+#[derive(Copy, Clone, PartialEq)]
+enum PuzzleAction {
+    /// Index on the current page
+    Level(usize),
+    PrevPage,
+    NextPage,
+    Back,
+}
+
+pub struct PuzzleSelectScene {
+    drawn: bool,
+    levels: Vec<Level>,
+    solved: Vec<String>,
+    page: usize,
+    /// Name and result of the level that was just played.
+    last_result: Option<(String, PuzzleOutcome)>,
+    /// Why levels were left out or couldn't be started. Newest first.
+    errors: Vec<String>,
+
+    level_buttons: List<PuzzleAction>,
+    prev_button: Button<PuzzleAction>,
+    next_button: Button<PuzzleAction>,
+    back_button: Button<PuzzleAction>,
+    pub back_button_pressed: bool,
+    /// Set when a level was chosen. Taken by main to start it.
+    pub selected_level: Option<Level>,
+}
+
+impl PuzzleSelectScene {
+    pub fn new(game_size: &Size) -> Self {
+        let (levels, errors) = puzzle::levels(game_size);
+        Self {
+            drawn: false,
+            levels,
+            solved: puzzle::solved_levels(),
+            page: 0,
+            last_result: None,
+            errors,
+            level_buttons: List::new(LEVEL_GAP),
+            prev_button: Button::new("«", 100.0, PuzzleAction::PrevPage),
+            next_button: Button::new("»", 100.0, PuzzleAction::NextPage),
+            back_button: Button::new("Back", 100.0, PuzzleAction::Back),
+            back_button_pressed: false,
+            selected_level: None,
+        }
+    }
+
+    pub fn with_result(mut self, level: &Level, outcome: PuzzleOutcome) -> Self {
+        // Open the page of the level that was played
+        if let Some(index) = self.levels.iter().position(|l| l.id == level.id) {
+            self.page = index / LEVELS_PER_PAGE;
+        }
+        self.last_result = Some((level.name.clone(), outcome));
+        self
+    }
+
+    /// E.g. when the chosen level couldn't be started.
+    pub fn show_error(&mut self, error: String) {
+        self.errors.insert(0, error);
+        self.last_result = None;
+        self.drawn = false;
+    }
+
+    fn page_count(&self) -> usize {
+        self.levels.len().div_ceil(LEVELS_PER_PAGE).max(1)
+    }
+
+    fn page_levels(&self) -> &[Level] {
+        let start = (self.page * LEVELS_PER_PAGE).min(self.levels.len());
+        let end = (start + LEVELS_PER_PAGE).min(self.levels.len());
+        &self.levels[start..end]
+    }
+
+    /// Every button needs to see all touch events to follow presses.
+    fn on_touch(&mut self, event: &MultitouchEvent) -> Option<PuzzleAction> {
+        [
+            self.level_buttons.on_touch(event),
+            self.prev_button.on_touch(event),
+            self.next_button.on_touch(event),
+            self.back_button.on_touch(event),
+        ].iter().find_map(|action| *action)
+    }
+
+    fn focusables(&mut self) -> Vec<&mut dyn Focusable> {
+        let mut focusables = self.level_buttons.focusables();
+        focusables.push(&mut self.prev_button);
+        focusables.push(&mut self.back_button);
+        focusables.push(&mut self.next_button);
+        focusables
+    }
+
+    fn activate(&self) -> Option<PuzzleAction> {
+        [
+            self.level_buttons.activate(),
+            self.prev_button.activate(),
+            self.next_button.activate(),
+            self.back_button.activate(),
+        ].iter().find_map(|action| *action)
+    }
+
+    fn on_action(&mut self, action: PuzzleAction) {
+        match action {
+            PuzzleAction::Level(index) => self.selected_level = self.page_levels().get(index).cloned(),
+            PuzzleAction::PrevPage | PuzzleAction::NextPage => {
+                if action == PuzzleAction::PrevPage {
+                    self.page -= 1;
+                }else {
+                    self.page += 1;
+                }
+                self.last_result = None;
+                self.drawn = false;
+            },
+            PuzzleAction::Back => self.back_button_pressed = true,
+        }
+    }
+}
+
+impl Scene for PuzzleSelectScene {
+    fn draw(&mut self, canvas: &mut Canvas) {
+        if self.drawn {
+            self.level_buttons.draw_feedback(canvas);
+            self.prev_button.draw_feedback(canvas);
+            self.next_button.draw_feedback(canvas);
+            self.back_button.draw_feedback(canvas);
+            return;
+        }
+        self.drawn = true;
+
+        canvas.clear();
+        canvas.draw_text(Point2 { x: None, y: Some(300) }, "Puzzles", 150.0);
+
+        let result = match self.last_result {
+            Some((ref name, PuzzleOutcome::Solved)) => Some(format!("{} solved!", name)),
+            Some((ref name, PuzzleOutcome::Failed)) => Some(format!("{} failed. Try again!", name)),
+            None => None,
+        };
+        if let Some(result) = result {
+            // Long names get a second line
+            let rect = mxcfb_rect { left: 100, top: 450 - ascent(60.0), width: DISPLAYWIDTH as u32 - 200, height: 150 };
+            canvas.draw_text_box(&TextBox::new(rect, 60.0).with_align(Align::Center).with_line_spacing(1.1), &result);
+        }else if let Some(error) = self.errors.first() {
+            let error = match self.errors.len() {
+                1 => error.clone(),
+                count => format!("{} (and {} more)", error, count - 1),
+            };
+            let rect = mxcfb_rect { left: 100, top: 450 - ascent(40.0), width: DISPLAYWIDTH as u32 - 200, height: 150 };
+            canvas.draw_text_box(&TextBox::new(rect, 40.0).with_align(Align::Center).with_line_spacing(1.1), &error);
+        }
+
+        if self.levels.is_empty() {
+            canvas.draw_text(Point2 { x: None, y: Some(LEVEL_Y_START + ascent(60.0) as i32) }, "No levels found", 60.0);
+        }
+        let labels: Vec<String> = self.page_levels().iter()
+            .map(|level| if self.solved.contains(&level.id) {
+                format!("{} (solved)", level.name)
+            }else {
+                level.name.clone()
+            })
+            .collect();
+        self.level_buttons = labels.iter().enumerate().fold(List::new(LEVEL_GAP), |list, (index, label)| {
+            list.with_button(Button::new(label, 60.0, PuzzleAction::Level(index)).with_gaps(15, 30))
+        });
+        column(&mut [&mut self.level_buttons], None, LEVEL_Y_START, 0);
+        self.level_buttons.draw(canvas);
+
+        // Only the buttons that get drawn can be used
+        self.prev_button.hide();
+        self.next_button.hide();
+        let buttons_y = 1780 - ascent(100.0) as i32 - 25;
+        if self.page_count() > 1 {
+            canvas.draw_text(Point2 { x: None, y: Some(1650) }, &format!("Page {} of {}", self.page + 1, self.page_count()), 40.0);
+            if self.page > 0 {
+                self.prev_button.set_pos(Point2 { x: 75, y: buttons_y });
+                self.prev_button.draw(canvas);
+            }
+            if self.page + 1 < self.page_count() {
+                self.next_button.set_pos(Point2 { x: DISPLAYWIDTH as i32 - 75 - self.next_button.size().x as i32, y: buttons_y });
+                self.next_button.draw(canvas);
+            }
+        }
+        column(&mut [&mut self.back_button], None, buttons_y, 0);
+        self.back_button.draw(canvas);
+
+        canvas.update_full();
+    }
+
+    fn next_tick(&self) -> Option<Duration> {
+        if self.drawn { None } else { Some(Duration::ZERO) }
+    }
+
+    fn invalidate(&mut self) {
+        self.drawn = false;
+    }
+
+    fn on_input(&mut self, event: InputEvent) {
+        let action = match event {
+            // Buttons trigger when released
+            InputEvent::MultitouchEvent { event } => self.on_touch(&event),
+            _ => match navigation(&event) {
+                Some(Navigation::Move(step)) => {
+                    move_focus(&mut self.focusables(), step);
+                    None
+                },
+                Some(Navigation::Activate) => self.activate(),
+                None => None,
+            },
+        };
+        if let Some(action) = action {
+            self.on_action(action);
+        }
+    }
+}
@@ -0,0 +1,179 @@
+use super::{save_config, Scene};
+use crate::canvas::*;
+use crate::config::{Config, ControlScheme, RefreshPolicy};
+use crate::widget::*;
+use libremarkable::input::InputEvent;
+use std::time::Duration;
+
+const ROW_Y_START: i32 = 480;
+const ROW_Y_GAP: i32 = 140;
+
+#[derive(Copy, Clone, PartialEq)]
+enum Setting {
+    ArrowButtons,
+    GhostPiece,
+    ControlScheme,
+    BlockPatterns,
+    RefreshPolicy,
+    Theme,
+    SwipeDistance,
+}
+
+const SETTINGS: [Setting; 7] = [
+    Setting::ArrowButtons,
+    Setting::GhostPiece,
+    Setting::ControlScheme,
+    Setting::BlockPatterns,
+    Setting::RefreshPolicy,
+    Setting::Theme,
+    Setting::SwipeDistance,
+];
+
+const CONTROL_SCHEMES: [ControlScheme; 3] = [ControlScheme::Swipe, ControlScheme::Drag, ControlScheme::TapZones];
+const REFRESH_POLICIES: [RefreshPolicy; 3] = [RefreshPolicy::Auto, RefreshPolicy::Fast, RefreshPolicy::Quality];
+const THEMES: [Theme; 2] = [Theme::Normal, Theme::Inverted];
+
+/// The value before or after the current one (wrapping around).
+fn cycle<T: Copy + PartialEq>(values: &[T], current: T, step: i32) -> T {
+    let index = values.iter().position(|value| *value == current).unwrap_or(0) as i32;
+    values[(index + step).rem_euclid(values.len() as i32) as usize]
+}
+
+fn on_off(value: bool) -> &'static str {
+    if value { "On" } else { "Off" }
+}
+
+impl Setting {
+    /// Key in the config file
+    fn key(&self) -> &'static str {
+        match self {
+            Setting::ArrowButtons => "arrow_buttons",
+            Setting::GhostPiece => "ghost_piece",
+            Setting::ControlScheme => "control_scheme",
+            Setting::BlockPatterns => "block_patterns",
+            Setting::RefreshPolicy => "refresh_policy",
+            Setting::Theme => "theme",
+            Setting::SwipeDistance => "swipe_chunk_distance",
+        }
+    }
+
+    fn describe(&self, config: &Config) -> String {
+        let description = match self {
+            Setting::ArrowButtons => format!("Arrow buttons: {}", on_off(config.arrow_buttons)),
+            Setting::GhostPiece => format!("Ghost piece: {}", on_off(config.ghost_piece)),
+            Setting::ControlScheme => format!("Controls: {}", match config.control_scheme {
+                ControlScheme::Swipe => "Swipe",
+                ControlScheme::Drag => "Drag",
+                ControlScheme::TapZones => "Tap zones",
+            }),
+            Setting::BlockPatterns => format!("Block patterns: {}", on_off(config.block_patterns)),
+            Setting::RefreshPolicy => format!("Refresh: {}", match config.refresh_policy {
+                RefreshPolicy::Auto => "Auto",
+                RefreshPolicy::Fast => "Fast",
+                RefreshPolicy::Quality => "Quality",
+            }),
+            Setting::Theme => format!("Theme: {}", match config.theme {
+                Theme::Normal => "Normal",
+                Theme::Inverted => "Inverted",
+            }),
+            Setting::SwipeDistance => format!("Swipe distance: {} px", config.swipe.chunk_dist),
+        };
+        if Config::is_overridden(self.key()) {
+            description + " *"
+        }else {
+            description
+        }
+    }
+
+    /// Changes the value by one step in the given direction (-1 or 1).
+    fn step(&self, config: &mut Config, step: i32) {
+        match self {
+            Setting::ArrowButtons => config.arrow_buttons = !config.arrow_buttons,
+            Setting::GhostPiece => config.ghost_piece = !config.ghost_piece,
+            Setting::ControlScheme => config.control_scheme = cycle(&CONTROL_SCHEMES, config.control_scheme, step),
+            Setting::BlockPatterns => config.block_patterns = !config.block_patterns,
+            Setting::RefreshPolicy => config.refresh_policy = cycle(&REFRESH_POLICIES, config.refresh_policy, step),
+            Setting::Theme => config.theme = cycle(&THEMES, config.theme, step),
+            Setting::SwipeDistance => config.swipe.chunk_dist = (config.swipe.chunk_dist as i32 + step * 5).clamp(5, 200) as u16,
+        }
+    }
+}
+
+pub struct SettingsScene {
+    drawn: bool,
+    /// Only the values of the config file. Overrides of the
+    /// command line must not end up in there.
+    config: Config,
+    /// Scene to go back to (e.g. a paused game).
+    previous: Option<Box<dyn Scene>>,
+
+    list: SettingsList<Setting>,
+    /// Set after saving or when leaving without saving.
+    pub done: bool,
+}
+
+impl SettingsScene {
+    pub fn new() -> Self {
+        Self {
+            drawn: false,
+            config: Config::stored(),
+            previous: None,
+            list: SettingsList::new(&SETTINGS, ROW_Y_START, ROW_Y_GAP, 970),
+            done: false,
+        }
+    }
+
+    pub fn with_previous(mut self, previous: Box<dyn Scene>) -> Self {
+        self.previous = Some(previous);
+        self
+    }
+
+    pub fn take_previous(&mut self) -> Option<Box<dyn Scene>> {
+        self.previous.take()
+    }
+
+}
+
+impl Scene for SettingsScene {
+    fn draw(&mut self, canvas: &mut Canvas) {
+        if !self.drawn {
+            self.drawn = true;
+
+            canvas.clear();
+            canvas.draw_text(Point2 { x: None, y: Some(350) }, "Settings", 125.0);
+
+            let config = &self.config;
+            self.list.draw(canvas, |setting| setting.describe(config));
+
+            if SETTINGS.iter().any(|setting| Config::is_overridden(setting.key())) {
+                canvas.draw_text(Point2 { x: Some(100), y: Some(self.list.row_y(SETTINGS.len()) + 70) }, "* Set on the command line, which takes precedence", 40.0);
+            }
+
+            canvas.update_full();
+            return;
+        }
+
+        let config = &self.config;
+        self.list.draw_changes(canvas, |setting| setting.describe(config));
+    }
+
+    fn next_tick(&self) -> Option<Duration> {
+        if self.drawn { None } else { Some(Duration::ZERO) }
+    }
+
+    fn invalidate(&mut self) {
+        self.drawn = false;
+    }
+
+    fn on_input(&mut self, event: InputEvent) {
+        match self.list.on_input(&event) {
+            Some(SettingsEvent::Step(setting, step)) => setting.step(&mut self.config, step),
+            Some(SettingsEvent::Save) => {
+                save_config(&self.config);
+                self.done = true;
+            },
+            Some(SettingsEvent::Back) => self.done = true,
+            None => {},
+        }
+    }
+}
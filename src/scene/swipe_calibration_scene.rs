@@ -0,0 +1,176 @@
+use super::{save_config, Scene};
+use crate::canvas::*;
+use crate::config::Config;
+use crate::swipe::{SwipeConfig, SwipeTracker, Swipe, Trigger, Direction};
+use crate::widget::*;
+use libremarkable::input::InputEvent;
+use std::time::Duration;
+
+const ROW_FONT_SIZE: f32 = 50.0;
+const ROW_Y_START: i32 = 580;
+const ROW_Y_GAP: i32 = 150;
+const TEST_AREA_TOP: u32 = 1250;
+const TEST_AREA_HEIGHT: u32 = 400;
+
+/// Every swipe that can be detected, so the player
+/// can see how the current values behave.
+const SWIPES: [Swipe; 12] = [
+    Swipe { direction: Direction::Up, trigger: Trigger::Flick { min_velocity: 1000 } },
+    Swipe { direction: Direction::Down, trigger: Trigger::Flick { min_velocity: 1000 } },
+    Swipe { direction: Direction::Left, trigger: Trigger::Flick { min_velocity: 1000 } },
+    Swipe { direction: Direction::Right, trigger: Trigger::Flick { min_velocity: 1000 } },
+    Swipe { direction: Direction::Up, trigger: Trigger::Completed },
+    Swipe { direction: Direction::Down, trigger: Trigger::Completed },
+    Swipe { direction: Direction::Left, trigger: Trigger::Completed },
+    Swipe { direction: Direction::Right, trigger: Trigger::Completed },
+    Swipe { direction: Direction::Up, trigger: Trigger::MinDistance(50) },
+    Swipe { direction: Direction::Down, trigger: Trigger::MinDistance(50) },
+    Swipe { direction: Direction::Left, trigger: Trigger::MinDistance(50) },
+    Swipe { direction: Direction::Right, trigger: Trigger::MinDistance(50) },
+];
+
+#[derive(Copy, Clone, PartialEq)]
+enum Setting {
+    ChunkDist,
+    CompletedMinChunkCount,
+    MinRatio,
+    AllowMultipleSwipesAtOnce,
+}
+
+const SETTINGS: [Setting; 4] = [
+    Setting::ChunkDist,
+    Setting::CompletedMinChunkCount,
+    Setting::MinRatio,
+    Setting::AllowMultipleSwipesAtOnce,
+];
+
+impl Setting {
+    fn describe(&self, config: &SwipeConfig) -> String {
+        match self {
+            Setting::ChunkDist => format!("Chunk distance: {} px", config.chunk_dist),
+            Setting::CompletedMinChunkCount => format!("Min chunks on release: {}", config.completed_min_chunk_count),
+            Setting::MinRatio => format!("Direction strictness: {:.1}", config.min_ratio),
+            Setting::AllowMultipleSwipesAtOnce => format!("Multiple swipes at once: {}", if config.allow_multiple_swipes_at_once { "On" } else { "Off" }),
+        }
+    }
+
+    /// Changes the value by one step in the given direction (-1 or 1).
+    fn step(&self, config: &mut SwipeConfig, step: i32) {
+        match self {
+            Setting::ChunkDist => config.chunk_dist = (config.chunk_dist as i32 + step * 5).clamp(5, 200) as u16,
+            Setting::CompletedMinChunkCount => config.completed_min_chunk_count = (config.completed_min_chunk_count as i32 + step).clamp(1, 10) as u16,
+            Setting::MinRatio => config.min_ratio = ((config.min_ratio * 10.0).round() + step as f32).clamp(10.0, 50.0) / 10.0,
+            Setting::AllowMultipleSwipesAtOnce => config.allow_multiple_swipes_at_once = !config.allow_multiple_swipes_at_once,
+        }
+    }
+}
+
+pub struct SwipeCalibrationScene {
+    drawn: bool,
+    config: Config,
+    swipe_tracker: SwipeTracker,
+    last_swipe: Option<Swipe>,
+    last_swipe_changed: bool,
+
+    list: SettingsList<Setting>,
+    /// Set after saving or when leaving without saving.
+    pub done: bool,
+}
+
+impl SwipeCalibrationScene {
+    pub fn new() -> Self {
+        let config = Config::stored();
+        Self {
+            drawn: false,
+            swipe_tracker: SwipeTracker::new(config.swipe),
+            config,
+            last_swipe: None,
+            last_swipe_changed: false,
+            list: SettingsList::new(&SETTINGS, ROW_Y_START, ROW_Y_GAP, 1000),
+            done: false,
+        }
+    }
+
+    fn on_setting(&mut self, setting: Setting, step: i32) {
+        setting.step(&mut self.config.swipe, step);
+        self.swipe_tracker.set_config(self.config.swipe);
+    }
+
+    fn draw_last_swipe(&mut self, canvas: &mut Canvas) -> mxcfb_rect {
+        let rect = mxcfb_rect {
+            left: 55,
+            top: TEST_AREA_TOP + 5,
+            width: DISPLAYWIDTH as u32 - 110,
+            height: TEST_AREA_HEIGHT - 10,
+        };
+        canvas.fill_background(
+            Point2 { x: rect.left as i32, y: rect.top as i32 },
+            Vector2 { x: rect.width, y: rect.height }
+        );
+        let text = match self.last_swipe {
+            Some(Swipe { direction, trigger: Trigger::Completed }) => format!("{:?} (on release)", direction),
+            Some(Swipe { direction, trigger: Trigger::MinDistance(dist) }) => format!("{:?} (every {} px)", direction, dist),
+            Some(Swipe { direction, trigger: Trigger::Flick { min_velocity } }) => format!("{:?} (flick over {} px/s)", direction, min_velocity),
+            None => "Swipe here to test".to_owned(),
+        };
+        canvas.draw_text(Point2 { x: None, y: Some((TEST_AREA_TOP + TEST_AREA_HEIGHT / 2 + 25) as i32) }, &text, ROW_FONT_SIZE);
+        rect
+    }
+}
+
+impl Scene for SwipeCalibrationScene {
+    fn draw(&mut self, canvas: &mut Canvas) {
+        if !self.drawn {
+            self.drawn = true;
+            self.last_swipe_changed = false;
+
+            canvas.clear();
+            canvas.draw_text(Point2 { x: None, y: Some(350) }, "Swipe Calibration", 125.0);
+
+            let swipe_config = &self.config.swipe;
+            self.list.draw(canvas, |setting| setting.describe(swipe_config));
+
+            canvas.draw_rect(Point2 { x: Some(50), y: Some(TEST_AREA_TOP as i32) }, Vector2 { x: DISPLAYWIDTH as u32 - 100, y: TEST_AREA_HEIGHT }, 2);
+            self.draw_last_swipe(canvas);
+
+            canvas.update_full();
+            return;
+        }
+
+        let swipe_config = &self.config.swipe;
+        self.list.draw_changes(canvas, |setting| setting.describe(swipe_config));
+
+        if self.last_swipe_changed {
+            self.last_swipe_changed = false;
+            let rect = self.draw_last_swipe(canvas);
+            canvas.update_partial(&rect);
+        }
+    }
+
+    fn next_tick(&self) -> Option<Duration> {
+        if self.drawn { None } else { Some(Duration::ZERO) }
+    }
+
+    fn invalidate(&mut self) {
+        self.drawn = false;
+    }
+
+    fn on_input(&mut self, event: InputEvent) {
+        match self.list.on_input(&event) {
+            Some(SettingsEvent::Step(setting, step)) => self.on_setting(setting, step),
+            Some(SettingsEvent::Save) => {
+                save_config(&self.config);
+                self.done = true;
+            },
+            Some(SettingsEvent::Back) => self.done = true,
+            None => {},
+        }
+
+        if let InputEvent::MultitouchEvent { event } = event {
+            if let Some(swipe) = self.swipe_tracker.detect(event, &SWIPES) {
+                self.last_swipe = Some(*swipe);
+                self.last_swipe_changed = true;
+            }
+        }
+    }
+}
@@ -0,0 +1,220 @@
+use super::{Scene, FRAME_DURATION};
+use crate::canvas::*;
+use crate::versus::{self, Connection, PendingConnection};
+use crate::widget::*;
+use libremarkable::input::{InputEvent, MultitouchEvent};
+use std::time::Duration;
+
+#[derive(Copy, Clone, PartialEq)]
+enum LobbyAction {
+    Host,
+    Join,
+    Back,
+}
+
+pub struct VersusLobbyScene {
+    drawn: bool,
+    status: String,
+    status_rect: Option<mxcfb_rect>,
+    status_changed: bool,
+    pending: Option<PendingConnection>,
+    /// Set once the other device is connected. Taken by
+    /// main to start the game.
+    pub connection: Option<Connection>,
+
+    host_button: Button<LobbyAction>,
+    /// Only if an address to join was given
+    join_button: Option<Button<LobbyAction>>,
+    back_button: Button<LobbyAction>,
+    pub back_button_pressed: bool,
+
+    join_addr: Option<String>,
+    port: u16,
+}
+
+impl VersusLobbyScene {
+    pub fn new() -> Self {
+        let join_addr = crate::CLI_OPTS.versus_join.clone();
+        Self {
+            drawn: false,
+            status: "Host or join a game".to_owned(),
+            status_rect: None,
+            status_changed: false,
+            pending: None,
+            connection: None,
+            host_button: Button::new("Host", 125.0, LobbyAction::Host),
+            join_button: join_addr.as_ref().map(|join_addr| Button::new(&format!("Join {}", join_addr), 75.0, LobbyAction::Join)),
+            back_button: Button::new("Back", 125.0, LobbyAction::Back),
+            back_button_pressed: false,
+            join_addr,
+            port: crate::CLI_OPTS.versus_port,
+        }
+    }
+
+    fn set_status(&mut self, status: String) {
+        self.status = status;
+        self.status_changed = true;
+    }
+
+    fn host(&mut self) {
+        match PendingConnection::host(self.port) {
+            Ok(pending) => {
+                self.pending = Some(pending);
+                let addr = versus::local_ip().unwrap_or_else(|| "this device".to_owned());
+                self.set_status(format!("Waiting on {}:{}", addr, self.port));
+            },
+            Err(err) => self.set_status(format!("Hosting failed: {}", err)),
+        }
+    }
+
+    fn join(&mut self) {
+        if let Some(ref join_addr) = self.join_addr {
+            self.pending = Some(PendingConnection::join(join_addr));
+            self.set_status(format!("Connecting to {}", join_addr));
+        }
+    }
+
+    /// Every button needs to see all touch events to follow presses.
+    fn on_touch(&mut self, event: &MultitouchEvent) -> Option<LobbyAction> {
+        [
+            self.host_button.on_touch(event),
+            self.join_button.as_mut().and_then(|button| button.on_touch(event)),
+            self.back_button.on_touch(event),
+        ].iter().find_map(|action| *action)
+    }
+
+    fn focusables(&mut self) -> Vec<&mut dyn Focusable> {
+        let mut focusables: Vec<&mut dyn Focusable> = vec![&mut self.host_button];
+        if let Some(ref mut join_button) = self.join_button {
+            focusables.push(join_button);
+        }
+        focusables.push(&mut self.back_button);
+        focusables
+    }
+
+    fn activate(&self) -> Option<LobbyAction> {
+        [
+            self.host_button.activate(),
+            self.join_button.as_ref().and_then(|button| button.activate()),
+            self.back_button.activate(),
+        ].iter().find_map(|action| *action)
+    }
+
+    fn on_action(&mut self, action: LobbyAction) {
+        if action == LobbyAction::Back {
+            self.back_button_pressed = true;
+        }
+        if self.pending.is_some() {
+            // Already hosting or joining
+            return;
+        }
+        match action {
+            LobbyAction::Host => self.host(),
+            LobbyAction::Join => self.join(),
+            LobbyAction::Back => {},
+        }
+    }
+
+    fn draw_status(&mut self, canvas: &mut Canvas) -> mxcfb_rect {
+        // Clear previous status
+        if let Some(rect) = self.status_rect {
+            canvas.fill_background(
+                Point2 { x: 0, y: rect.top as i32 },
+                Vector2 { x: DISPLAYWIDTH as u32, y: rect.height }
+            );
+        }
+        let rect = canvas.draw_text(Point2 { x: None, y: Some(800) }, &self.status, 50.0);
+        let cleared_rect = mxcfb_rect {
+            top: rect.top.min(self.status_rect.map(|r| r.top).unwrap_or(rect.top)),
+            left: 0,
+            width: DISPLAYWIDTH as u32,
+            height: rect.height.max(self.status_rect.map(|r| r.height).unwrap_or(0)),
+        };
+        self.status_rect = Some(rect);
+        cleared_rect
+    }
+}
+
+impl Scene for VersusLobbyScene {
+    fn draw(&mut self, canvas: &mut Canvas) {
+        if let Some(ref mut pending) = self.pending {
+            match pending.poll() {
+                Some(Ok(connection)) => {
+                    self.pending = None;
+                    self.connection = Some(connection);
+                },
+                Some(Err(err)) => {
+                    self.pending = None;
+                    self.set_status(format!("Connection failed: {}", err));
+                },
+                None => { }
+            }
+        }
+
+        if !self.drawn {
+            self.drawn = true;
+            self.status_changed = false;
+
+            canvas.clear();
+            canvas.draw_text(Point2 { x: None, y: Some(500) }, "Versus", 250.0);
+            self.draw_status(canvas);
+
+            let host_y = 1000 - ascent(125.0) as i32 - 25;
+            column(&mut [&mut self.host_button], None, host_y, 0);
+            self.host_button.draw(canvas);
+            if let Some(ref mut join_button) = self.join_button {
+                column(&mut [join_button], None, host_y + self.host_button.size().y as i32 + 150 - 25, 0);
+                join_button.draw(canvas);
+            }
+            column(&mut [&mut self.back_button], None, 1650 - ascent(125.0) as i32 - 25, 0);
+            self.back_button.draw(canvas);
+
+            canvas.update_full();
+        }
+
+        if self.status_changed {
+            self.status_changed = false;
+            let rect = self.draw_status(canvas);
+            canvas.update_partial(&rect);
+        }
+
+        self.host_button.draw_feedback(canvas);
+        if let Some(ref mut join_button) = self.join_button {
+            join_button.draw_feedback(canvas);
+        }
+        self.back_button.draw_feedback(canvas);
+    }
+
+    fn next_tick(&self) -> Option<Duration> {
+        if !self.drawn {
+            Some(Duration::ZERO)
+        }else if self.pending.is_some() {
+            // Waiting for the connection
+            Some(FRAME_DURATION)
+        }else {
+            None
+        }
+    }
+
+    fn invalidate(&mut self) {
+        self.drawn = false;
+    }
+
+    fn on_input(&mut self, event: InputEvent) {
+        let action = match event {
+            // Buttons trigger when released
+            InputEvent::MultitouchEvent { event } => self.on_touch(&event),
+            _ => match navigation(&event) {
+                Some(Navigation::Move(step)) => {
+                    move_focus(&mut self.focusables(), step);
+                    None
+                },
+                Some(Navigation::Activate) => self.activate(),
+                None => None,
+            },
+        };
+        if let Some(action) = action {
+            self.on_action(action);
+        }
+    }
+}
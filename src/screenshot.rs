@@ -0,0 +1,30 @@
+//! Screenshots of the whole screen as PNG.
+//!
+//! Taken by tapping the clock at the top right or with SIGRTMIN.
+//! If the scene has a state (the board of a game), it is saved
+//! next to it as puzzle level.
+
+use crate::canvas::Canvas;
+use crate::clock;
+use crate::scene::Scene;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Saves the screen and returns the path of the image.
+pub fn take(canvas: &mut Canvas, scene: &dyn Scene, dir: &Path) -> Result<PathBuf, String> {
+    fs::create_dir_all(dir).map_err(|err| format!("Failed to create {:?}: {}", dir, err))?;
+    let now = clock::now();
+    let name = format!(
+        "retris_{:04}-{:02}-{:02}_{:02}-{:02}-{:02}",
+        now.year, now.month, now.day, now.hour, now.minute, now.second
+    );
+
+    let image_path = dir.join(format!("{}.png", name));
+    canvas.save_image(&image_path)?;
+
+    if let Some(text) = scene.state_text() {
+        let state_path = dir.join(format!("{}.level", name));
+        fs::write(&state_path, text).map_err(|err| format!("Failed to write {:?}: {}", state_path, err))?;
+    }
+    Ok(image_path)
+}
@@ -0,0 +1,100 @@
+//! Leaving the device usable, also after a crash or SIGTERM.
+//!
+//! With --kill-xochitl nothing else brings back xochitl, so every way
+//! out has to start it again before anything else can go wrong. A panic
+//! (in any thread) ends the whole process after writing a crash log
+//! next to the config file.
+
+use crate::canvas::{Canvas, Theme};
+use crate::clock;
+use crate::config::Config;
+use std::backtrace::Backtrace;
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+/// Exit code of a panicking thread in Rust.
+const PANIC_EXIT_CODE: i32 = 101;
+const START_XOCHITL: [&str; 3] = ["systemctl", "start", "xochitl"];
+
+static XOCHITL_STOPPED: AtomicBool = AtomicBool::new(false);
+/// Set once leaving. Panics while clearing the screen are handled there.
+static EXITING: AtomicBool = AtomicBool::new(false);
+
+pub fn crash_log_path() -> PathBuf {
+    Config::path().with_file_name("crash.log")
+}
+
+/// Installs the panic hook. Xochitl gets started again on
+/// exit if it was stopped.
+pub fn install(xochitl_stopped: bool) {
+    XOCHITL_STOPPED.store(xochitl_stopped, Ordering::SeqCst);
+
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        // Still print it like without the hook
+        default_hook(info);
+        if EXITING.load(Ordering::SeqCst) {
+            return;
+        }
+
+        let now = clock::now();
+        let log = format!(
+            "retris {} crashed at {:04}-{:02}-{:02} {:02}:{:02}:{:02}\n\n{}\n\nBacktrace:\n{}\n",
+            env!("CARGO_PKG_VERSION"),
+            now.year, now.month, now.day, now.hour, now.minute, now.second,
+            info, Backtrace::force_capture()
+        );
+        let path = crash_log_path();
+        match path.parent().map_or(Ok(()), fs::create_dir_all).and_then(|_| fs::write(&path, log)) {
+            Ok(()) => eprintln!("Wrote crash log to {:?}", path),
+            Err(err) => eprintln!("Failed to write crash log to {:?}: {}", path, err),
+        }
+
+        // The canvas of the main loop may be in use or broken by the
+        // panic, so another one is used. A panic inside this hook would
+        // abort, so that happens in its own thread.
+        EXITING.store(true, Ordering::SeqCst);
+        leave(XOCHITL_STOPPED.load(Ordering::SeqCst), &START_XOCHITL, || {
+            if thread::spawn(|| clear(&mut Canvas::new())).join().is_err() {
+                eprintln!("Failed to clear the screen");
+            }
+        });
+        std::process::exit(PANIC_EXIT_CODE);
+    }));
+}
+
+/// Starts xochitl again if it was stopped, clears
+/// the screen and ends the process.
+pub fn exit(canvas: &mut Canvas, code: i32) -> ! {
+    EXITING.store(true, Ordering::SeqCst);
+    leave(XOCHITL_STOPPED.load(Ordering::SeqCst), &START_XOCHITL, || clear(canvas));
+    std::process::exit(code);
+}
+
+fn clear(canvas: &mut Canvas) {
+    canvas.set_theme(Theme::Normal);
+    canvas.clear();
+    canvas.update_full();
+}
+
+/// Starts xochitl first, so the device stays usable even
+/// if clearing the screen fails (which is only tried).
+fn leave(xochitl_stopped: bool, start_command: &[&str], clear: impl FnOnce()) {
+    if xochitl_stopped {
+        match Command::new(start_command[0]).args(&start_command[1..]).status() {
+            Ok(status) if !status.success() => eprintln!("Failed to start xochitl: {}", status),
+            Ok(_) => { },
+            Err(err) => eprintln!("Failed to start xochitl: {}", err),
+        }
+    }
+    if panic::catch_unwind(AssertUnwindSafe(clear)).is_err() {
+        eprintln!("Failed to clear the screen");
+    }
+}
+
+#[cfg(test)]
+mod tests;
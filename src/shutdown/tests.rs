@@ -0,0 +1,54 @@
+//! Starts a fake xochitl that only creates a file.
+
+use super::*;
+use std::cell::Cell;
+use std::path::Path;
+
+fn marker(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("retris-test-{}-{}", std::process::id(), name));
+    let _ = fs::remove_file(&path);
+    path
+}
+
+/// Shell script for `sh -c` that creates the marker.
+fn touch(marker: &Path) -> String {
+    format!("touch '{}'", marker.display())
+}
+
+#[test]
+fn xochitl_starts_before_the_screen_gets_cleared() {
+    let marker = marker("start-first");
+    let script = touch(&marker);
+    let started_when_clearing = Cell::new(None);
+    leave(true, &["sh", "-c", &script], || started_when_clearing.set(Some(marker.exists())));
+    assert_eq!(started_when_clearing.get(), Some(true));
+    fs::remove_file(marker).unwrap();
+}
+
+#[test]
+fn failing_to_clear_is_not_fatal() {
+    let marker = marker("clear-fails");
+    let script = touch(&marker);
+    leave(true, &["sh", "-c", &script], || panic!("No framebuffer"));
+    assert!(marker.exists());
+    fs::remove_file(marker).unwrap();
+}
+
+#[test]
+fn running_xochitl_is_left_alone() {
+    let marker = marker("not-stopped");
+    let script = touch(&marker);
+    let cleared = Cell::new(false);
+    leave(false, &["sh", "-c", &script], || cleared.set(true));
+    assert!(cleared.get());
+    assert!(!marker.exists());
+}
+
+#[test]
+fn failing_to_start_xochitl_still_clears() {
+    let cleared = Cell::new(false);
+    leave(true, &["/nonexistent/systemctl"], || cleared.set(true));
+    assert!(cleared.get());
+    leave(true, &["false"], || cleared.set(false));
+    assert!(!cleared.get());
+}
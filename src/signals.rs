@@ -0,0 +1,149 @@
+//! Signals of launchers and the shell.
+//!
+//! Launchers like oxide send SIGUSR2 before putting the app into the
+//! background and SIGUSR1 when bringing it back. The shell uses SIGTSTP
+//! (Ctrl+Z) and SIGCONT. Other apps use the screen in between, so
+//! everything has to be drawn again afterwards.
+//!
+//! SIGRTMIN takes a screenshot (e.g. `kill -RTMIN $(pidof retris)`).
+//!
+//! SIGTERM and SIGINT (Ctrl+C) exit like the exit button, so the
+//! screen gets cleared and xochitl comes back.
+//!
+//! The handlers only set flags and wake up the main loop, which
+//! then checks them.
+
+use libremarkable::input::InputEvent;
+use std::fs::File;
+use std::io::{self, Read};
+use std::os::unix::io::FromRawFd;
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::{Condvar, Mutex, PoisonError};
+use std::thread;
+use std::time::Duration;
+
+static SUSPEND_REQUESTED: AtomicBool = AtomicBool::new(false);
+/// SIGTSTP expects the process to stop. Since it is handled,
+/// that has to be done manually.
+static STOP_REQUESTED: AtomicBool = AtomicBool::new(false);
+static RESUME_REQUESTED: AtomicBool = AtomicBool::new(false);
+static SCREENSHOT_REQUESTED: AtomicBool = AtomicBool::new(false);
+static EXIT_REQUESTED: AtomicBool = AtomicBool::new(false);
+/// Write end of a pipe that gets a byte for every signal.
+static WAKE_UP_FD: AtomicI32 = AtomicI32::new(-1);
+/// Notified after every signal for wait_for_resume().
+static WOKEN_UP: Condvar = Condvar::new();
+static WOKEN_UP_LOCK: Mutex<()> = Mutex::new(());
+
+extern "C" fn on_signal(signal: libc::c_int) {
+    match signal {
+        libc::SIGUSR2 => SUSPEND_REQUESTED.store(true, Ordering::SeqCst),
+        libc::SIGTSTP => {
+            STOP_REQUESTED.store(true, Ordering::SeqCst);
+            SUSPEND_REQUESTED.store(true, Ordering::SeqCst);
+        },
+        libc::SIGUSR1 | libc::SIGCONT => RESUME_REQUESTED.store(true, Ordering::SeqCst),
+        libc::SIGTERM | libc::SIGINT => EXIT_REQUESTED.store(true, Ordering::SeqCst),
+        signal if signal == libc::SIGRTMIN() => SCREENSHOT_REQUESTED.store(true, Ordering::SeqCst),
+        _ => { }
+    }
+    // Sending into the channel isn't allowed in here, writing is
+    let fd = WAKE_UP_FD.load(Ordering::SeqCst);
+    if fd >= 0 {
+        unsafe { libc::write(fd, [0u8].as_ptr() as *const libc::c_void, 1); }
+    }
+}
+
+/// Handles the signals. After each one `wake_up` gets an
+/// `InputEvent::Unknown`, so the main loop can block on it.
+pub fn install(wake_up: Sender<InputEvent>) {
+    if let Err(err) = forward_wake_ups(wake_up) {
+        eprintln!("Failed to create the pipe for signals: {}", err);
+    }
+
+    for signal in [libc::SIGUSR1, libc::SIGUSR2, libc::SIGTSTP, libc::SIGCONT, libc::SIGTERM, libc::SIGINT, libc::SIGRTMIN()] {
+        unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+            // Don't interrupt the blocking reads of the input threads
+            action.sa_flags = libc::SA_RESTART;
+            libc::sigemptyset(&mut action.sa_mask);
+            if libc::sigaction(signal, &action, std::ptr::null_mut()) != 0 {
+                eprintln!("Failed to handle signal {}: {}", signal, std::io::Error::last_os_error());
+            }
+        }
+    }
+}
+
+/// Whether the app should go into the background.
+pub fn take_suspend_request() -> bool {
+    SUSPEND_REQUESTED.swap(false, Ordering::SeqCst)
+}
+
+/// Whether the app got resumed without being suspended
+/// first (e.g. after a SIGSTOP of a launcher).
+pub fn take_resume_request() -> bool {
+    RESUME_REQUESTED.swap(false, Ordering::SeqCst)
+}
+
+pub fn take_screenshot_request() -> bool {
+    SCREENSHOT_REQUESTED.swap(false, Ordering::SeqCst)
+}
+
+pub fn take_exit_request() -> bool {
+    EXIT_REQUESTED.swap(false, Ordering::SeqCst)
+}
+
+fn forward_wake_ups(wake_up: Sender<InputEvent>) -> io::Result<()> {
+    let mut fds = [0; 2];
+    unsafe {
+        if libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // A full pipe must not block the handler. One byte is enough anyway.
+        libc::fcntl(fds[1], libc::F_SETFL, libc::O_NONBLOCK);
+    }
+    let mut pipe = unsafe { File::from_raw_fd(fds[0]) };
+    WAKE_UP_FD.store(fds[1], Ordering::SeqCst);
+    thread::spawn(move || {
+        let mut buf = [0u8; 16];
+        loop {
+            match pipe.read(&mut buf) {
+                Ok(0) => break,
+                Ok(_) => {
+                    // Locking makes sure wait_for_resume() either sees
+                    // the flags already or is waiting for this
+                    drop(WOKEN_UP_LOCK.lock().unwrap_or_else(PoisonError::into_inner));
+                    WOKEN_UP.notify_all();
+                    if wake_up.send(InputEvent::Unknown {}).is_err() {
+                        break;
+                    }
+                },
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => { },
+                Err(err) => {
+                    eprintln!("Failed to read the pipe for signals: {}", err);
+                    break;
+                },
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Stops the process if asked to (SIGTSTP) and blocks until
+/// resumed. Returns early when asked to exit.
+pub fn wait_for_resume() {
+    if STOP_REQUESTED.swap(false, Ordering::SeqCst) {
+        unsafe { libc::raise(libc::SIGSTOP); }
+    }
+    // Without the pipe nothing notifies, so checking now and then has to do
+    let timeout = if WAKE_UP_FD.load(Ordering::SeqCst) >= 0 { None } else { Some(Duration::from_millis(100)) };
+    let mut lock = WOKEN_UP_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+    while !RESUME_REQUESTED.swap(false, Ordering::SeqCst) && !EXIT_REQUESTED.load(Ordering::SeqCst) {
+        lock = match timeout {
+            Some(timeout) => WOKEN_UP.wait_timeout(lock, timeout).unwrap_or_else(PoisonError::into_inner).0,
+            None => WOKEN_UP.wait(lock).unwrap_or_else(PoisonError::into_inner),
+        };
+    }
+}
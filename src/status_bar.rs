@@ -0,0 +1,103 @@
+//! Clock and battery level at the top right of the screen.
+//!
+//! The battery is read from a power_supply directory in sysfs
+//! (`capacity` and `status`). By default the first one of type
+//! "Battery" is used. Everything is only redrawn when the minute
+//! changes, so this costs at most one small refresh per minute.
+
+use crate::canvas::*;
+use crate::clock;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const POWER_SUPPLY_DIR: &str = "/sys/class/power_supply";
+const FONT_SIZE: f32 = 40.0;
+/// Area on the right of the top row. The scenes keep their buttons left of it.
+const AREA: mxcfb_rect = mxcfb_rect { left: DISPLAYWIDTH as u32 - 500, top: 30, width: 450, height: 65 };
+
+/// Whether the position is on the status bar (tapping it takes a screenshot).
+pub fn is_hitting(pos: Point2<u16>) -> bool {
+    Canvas::is_hitting(pos, AREA)
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Battery {
+    /// In percent
+    pub capacity: u8,
+    pub charging: bool,
+}
+
+/// Reads the battery state from a sysfs power_supply directory.
+pub fn read_battery(dir: &Path) -> Option<Battery> {
+    let capacity = fs::read_to_string(dir.join("capacity")).ok()?.trim().parse().ok()?;
+    let status = fs::read_to_string(dir.join("status")).unwrap_or_default();
+    Some(Battery { capacity, charging: status.trim() == "Charging" })
+}
+
+/// The power_supply directory of the battery of this device.
+pub fn find_battery() -> Option<PathBuf> {
+    let mut dirs: Vec<PathBuf> = fs::read_dir(POWER_SUPPLY_DIR).ok()?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect();
+    dirs.sort();
+    dirs.into_iter().find(|dir| fs::read_to_string(dir.join("type")).map(|t| t.trim() == "Battery").unwrap_or(false))
+}
+
+pub fn text(hour: i32, minute: i32, battery: Option<Battery>) -> String {
+    match battery {
+        Some(Battery { capacity, charging: true }) => format!("Charging {}%   {:02}:{:02}", capacity, hour, minute),
+        Some(Battery { capacity, charging: false }) => format!("Battery {}%   {:02}:{:02}", capacity, hour, minute),
+        None => format!("{:02}:{:02}", hour, minute),
+    }
+}
+
+pub struct StatusBar {
+    power_supply: Option<PathBuf>,
+    /// Hour and minute currently shown. None if not drawn yet.
+    shown_time: Option<(i32, i32)>,
+}
+
+impl StatusBar {
+    pub fn new(power_supply: Option<PathBuf>) -> Self {
+        Self { power_supply, shown_time: None }
+    }
+
+    /// Uses the power_supply_path of the config or looks for the battery.
+    pub fn from_config(config: &crate::config::Config) -> Self {
+        Self::new(config.power_supply_path.clone().or_else(find_battery))
+    }
+
+    /// Draw again on the next call of draw().
+    pub fn invalidate(&mut self) {
+        self.shown_time = None;
+    }
+
+    /// Time until the minute changes.
+    pub fn next_tick(&self) -> Duration {
+        Duration::from_secs((60 - clock::now().second).clamp(1, 60) as u64)
+    }
+
+    /// Draws the status if the minute changed since the last time.
+    /// Returns the area that needs to be refreshed.
+    pub fn draw(&mut self, canvas: &mut Canvas) -> Option<mxcfb_rect> {
+        let now = clock::now();
+        let (hour, minute) = (now.hour, now.minute);
+        if self.shown_time == Some((hour, minute)) {
+            return None;
+        }
+        self.shown_time = Some((hour, minute));
+
+        let battery = self.power_supply.as_ref().and_then(|dir| read_battery(dir));
+        let text = text(hour, minute, battery);
+        canvas.fill_background(
+            Point2 { x: AREA.left as i32, y: AREA.top as i32 },
+            Vector2 { x: AREA.width, y: AREA.height }
+        );
+        canvas.draw_text_box(&TextBox::new(AREA, FONT_SIZE).with_align(Align::Right), &text);
+        Some(AREA)
+    }
+}
+
+#[cfg(test)]
+mod tests;
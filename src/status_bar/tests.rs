@@ -0,0 +1,47 @@
+//! Reads a fake power_supply directory instead of the one in sysfs.
+
+use super::*;
+
+fn fake_power_supply(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("retris-test-{}-{}", std::process::id(), name));
+    fs::create_dir_all(&dir).unwrap();
+    for (file, contents) in files {
+        fs::write(dir.join(file), contents).unwrap();
+    }
+    dir
+}
+
+#[test]
+fn discharging() {
+    let dir = fake_power_supply("discharging", &[("capacity", "85\n"), ("status", "Discharging\n")]);
+    assert_eq!(read_battery(&dir), Some(Battery { capacity: 85, charging: false }));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn charging() {
+    let dir = fake_power_supply("charging", &[("capacity", "7\n"), ("status", "Charging\n")]);
+    assert_eq!(read_battery(&dir), Some(Battery { capacity: 7, charging: true }));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn missing_status() {
+    let dir = fake_power_supply("missing_status", &[("capacity", "100\n")]);
+    assert_eq!(read_battery(&dir), Some(Battery { capacity: 100, charging: false }));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn no_battery() {
+    let dir = fake_power_supply("no_battery", &[("status", "Full\n")]);
+    assert_eq!(read_battery(&dir), None);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn texts() {
+    assert_eq!(text(9, 5, Some(Battery { capacity: 85, charging: false })), "Battery 85%   09:05");
+    assert_eq!(text(23, 59, Some(Battery { capacity: 7, charging: true })), "Charging 7%   23:59");
+    assert_eq!(text(0, 0, None), "00:00");
+}
@@ -1,23 +1,41 @@
 use fxhash::FxHashMap;
 use libremarkable::cgmath::Point2;
 use libremarkable::input::MultitouchEvent;
+use std::time::{Duration, Instant};
 
-pub const SWIPE_DIRECTION_CHUNK_DIST: u16 = 25; // px
-/// Min TrackedFinger.swipe_chunk_count to cound as completed swipe
-pub const SWIPE_DIRECTION_COMPLETED_MIN_CHUNK_COUNT: u16 = 2;
-/// The higher this value, the stricter the direction of the 
-/// gesture has to be.
-/// Is calculated on a per GESTURE_DIRECTION_CHUNK_DIST basis
-/// by the following formula: abs(intended_direction_dist) / abs(orthogonal_direction_dist)
-/// For the gesture "Up" that could be e.g: abs(-100) / abs(49) => 2.04#
-/// The value 2.0 basically allows for NORTH, EAST, WEST, SOUTH but not
-/// NORTH_EAST, NORTH_WEST, SOUTH_EAST, SOUTH_WEST if using a compass
-/// as analogy for the angles.
-pub const SWIPE_DIRECTION_MIN_RATIO: f32 = 1.5;
-
-/// Allow a new gesture to register, even if the current
-/// swipe was messed up.
-pub const SWIPE_ALLOW_MULTIPLE_SWIPES_AT_ONCE: bool = true;
+/// Tuning of the swipe recognition. Can be changed
+/// in the config file or the swipe calibration.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SwipeConfig {
+    /// Distance in px a finger has to move until its
+    /// direction gets evaluated again.
+    pub chunk_dist: u16,
+    /// Min TrackedFinger.swipe_chunk_count to cound as completed swipe
+    pub completed_min_chunk_count: u16,
+    /// The higher this value, the stricter the direction of the 
+    /// gesture has to be.
+    /// Is calculated on a per chunk_dist basis
+    /// by the following formula: abs(intended_direction_dist) / abs(orthogonal_direction_dist)
+    /// For the gesture "Up" that could be e.g: abs(-100) / abs(49) => 2.04#
+    /// The value 2.0 basically allows for NORTH, EAST, WEST, SOUTH but not
+    /// NORTH_EAST, NORTH_WEST, SOUTH_EAST, SOUTH_WEST if using a compass
+    /// as analogy for the angles.
+    pub min_ratio: f32,
+    /// Allow a new gesture to register, even if the current
+    /// swipe was messed up.
+    pub allow_multiple_swipes_at_once: bool,
+}
+
+impl Default for SwipeConfig {
+    fn default() -> Self {
+        Self {
+            chunk_dist: 25,
+            completed_min_chunk_count: 2,
+            min_ratio: 1.5,
+            allow_multiple_swipes_at_once: true,
+        }
+    }
+}
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum Trigger {
@@ -29,8 +47,12 @@ pub enum Trigger {
     /// a new swipe could be recognized
     /// immediately again.
     /// The distance is detected on a basis of
-    /// SWIPE_DIRECTION_CHUNK_DIST.
+    /// SwipeConfig.chunk_dist.
     MinDistance(u16),
+    /// The swipe gesture will fire, when the user
+    /// releases his finger while still moving
+    /// with at least the given velocity (px/s).
+    Flick { min_velocity: u16 },
 }
 
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -45,33 +67,86 @@ pub enum Direction { Up, Down, Left, Right }
 struct TrackedFinger {
     swipe_chunk_count: u16,
     last_pos: Point2<u16>,
+    /// When the finger was at last_pos
+    last_time: Instant,
     current_pos: Point2<u16>,
+    /// Velocity (px/s) of the most recent chunk
+    velocity: f32,
     pub invalidated: bool, // If true, direction should be None!
     pub direction: Option<Direction>,
+    /// Direction of the swipe that fired before restart(),
+    /// so a flick right after it is still recognized
+    previous_direction: Option<Direction>,
 }
 
 impl TrackedFinger {
-    pub fn new(start_pos: Point2<u16>) -> Self{
+    pub fn new(start_pos: Point2<u16>, start_time: Instant) -> Self{
         Self {
             swipe_chunk_count: 0,
             last_pos: start_pos,
+            last_time: start_time,
             direction: None,
             current_pos: start_pos,
+            velocity: 0.0,
             invalidated: false,
+            previous_direction: None,
         }
     }
+
+    /// Start recognizing a new swipe from the last position
+    /// without forgetting how fast and where the finger is moving.
+    fn restart(&mut self) {
+        let velocity = self.velocity;
+        let direction = self.direction;
+        *self = TrackedFinger::new(self.last_pos, self.last_time);
+        self.velocity = velocity;
+        self.previous_direction = direction;
+    }
+
+    /// Direction a flick goes into when released now.
+    /// Still the previous one if the finger got released
+    /// before moving on for another chunk.
+    fn flick_direction(&self) -> Option<Direction> {
+        if self.invalidated {
+            None
+        }else {
+            self.direction.or(self.previous_direction)
+        }
+    }
+
+    /// Moves last_pos to the current position and
+    /// measures the velocity of this chunk.
+    fn advance(&mut self, now: Instant) {
+        let dist = self.highest_orthogonal_dist() as f32;
+        let elapsed = now.duration_since(self.last_time).as_secs_f32();
+        self.velocity = if elapsed > 0.0 { dist / elapsed } else { f32::MAX };
+        self.last_pos = self.current_pos;
+        self.last_time = now;
+    }
+
+    /// Velocity at the time of releasing. Gets lower
+    /// the longer the finger rested before.
+    fn release_velocity(&self, now: Instant, config: &SwipeConfig) -> f32 {
+        let idle = now.duration_since(self.last_time).as_secs_f32();
+        if idle > 0.0 {
+            self.velocity.min(config.chunk_dist as f32 / idle)
+        }else {
+            self.velocity
+        }
+    }
+
     fn highest_orthogonal_dist(&self) -> u16 {
-        let x_dist = (self.current_pos.x as i16 - self.last_pos.x as i16).abs() as u16;
-        let y_dist = (self.current_pos.y as i16 - self.last_pos.y as i16).abs() as u16;
+        let x_dist = (self.current_pos.x as i16 - self.last_pos.x as i16).unsigned_abs();
+        let y_dist = (self.current_pos.y as i16 - self.last_pos.y as i16).unsigned_abs();
         std::cmp::max(x_dist, y_dist)
     }
 
-    fn update(&mut self, current_pos: Point2<u16>) {
+    fn update(&mut self, current_pos: Point2<u16>, now: Instant, config: &SwipeConfig) {
         self.current_pos = current_pos;
-        if self.highest_orthogonal_dist() < SWIPE_DIRECTION_CHUNK_DIST {
+        if self.highest_orthogonal_dist() < config.chunk_dist {
             return;
         }
-        if self.invalidated && !SWIPE_ALLOW_MULTIPLE_SWIPES_AT_ONCE {
+        if self.invalidated && !config.allow_multiple_swipes_at_once {
             // The direction changed. This gesture should not
             // be recognized as anything valid anymore.
             // It should not contain any direction.
@@ -81,13 +156,13 @@ impl TrackedFinger {
         let x_dist = self.current_pos.x as i16 - self.last_pos.x as i16;
         let y_dist = self.current_pos.y as i16 - self.last_pos.y as i16;
 
-        let current_dir = if x_dist > y_dist.abs() && x_dist.abs() as f32 / y_dist.abs().max(1) as f32 > SWIPE_DIRECTION_MIN_RATIO {
+        let current_dir = if x_dist > y_dist.abs() && x_dist.abs() as f32 / y_dist.abs().max(1) as f32 > config.min_ratio {
             Some(Direction::Right)
-        }else if -x_dist > y_dist.abs() && x_dist.abs() as f32 / y_dist.abs().max(1) as f32 > SWIPE_DIRECTION_MIN_RATIO {
+        }else if -x_dist > y_dist.abs() && x_dist.abs() as f32 / y_dist.abs().max(1) as f32 > config.min_ratio {
             Some(Direction::Left)
-        }else if y_dist > x_dist.abs() && y_dist.abs() as f32 / x_dist.abs().max(1) as f32 > SWIPE_DIRECTION_MIN_RATIO {
+        }else if y_dist > x_dist.abs() && y_dist.abs() as f32 / x_dist.abs().max(1) as f32 > config.min_ratio {
             Some(Direction::Down)
-        }else if -y_dist > x_dist.abs() && y_dist.abs() as f32 / x_dist.abs().max(1) as f32 > SWIPE_DIRECTION_MIN_RATIO {
+        }else if -y_dist > x_dist.abs() && y_dist.abs() as f32 / x_dist.abs().max(1) as f32 > config.min_ratio {
             Some(Direction::Up)
         }else {
             None
@@ -109,13 +184,13 @@ impl TrackedFinger {
                 self.direction = None;
             }else {
                 // Direction stayed the same
-                self.last_pos = current_pos;
+                self.advance(now);
                 self.swipe_chunk_count += 1;
             }
         }else {
             // This direction is the initial one and expected
             // to last till the gesture is announced.
-            self.last_pos = self.current_pos;
+            self.advance(now);
             self.direction = Some(current_dir);
         }
     }
@@ -123,30 +198,41 @@ impl TrackedFinger {
 
 pub struct SwipeTracker {
     trackings: FxHashMap<i32 /* Tracking id */, TrackedFinger>,
+    config: SwipeConfig,
 }
 
 impl SwipeTracker {
-    pub fn new() -> Self {
-        Self { trackings: Default::default() }
+    pub fn new(config: SwipeConfig) -> Self {
+        Self { trackings: Default::default(), config }
+    }
+
+    pub fn set_config(&mut self, config: SwipeConfig) {
+        self.config = config;
     }
 
     pub fn detect<'a>(&mut self, event: MultitouchEvent, conditions: &'a[Swipe]) -> Option<&'a Swipe> {
+        self.detect_at(event, conditions, Instant::now())
+    }
+
+    /// Same as detect() but with the time the event happened.
+    pub fn detect_at<'a>(&mut self, event: MultitouchEvent, conditions: &'a[Swipe], now: Instant) -> Option<&'a Swipe> {
         match event {
             MultitouchEvent::Press { finger } => {
-                self.trackings.insert(finger.tracking_id, TrackedFinger::new(finger.pos));
+                self.trackings.insert(finger.tracking_id, TrackedFinger::new(finger.pos, now));
             },
             MultitouchEvent::Move { finger } => {
                 let mut potential_swipe: Option<&'a Swipe> = None;
+                let config = &self.config;
                 self.trackings.entry(finger.tracking_id).and_modify(|tracked_finger|
-                    tracked_finger.update(finger.pos)
+                    tracked_finger.update(finger.pos, now, config)
                 ).and_modify(|tracked_finger| {
                     if let Some(intermediate_direction) = tracked_finger.direction {
                         for swipe in conditions.iter() {
                             if swipe.direction == intermediate_direction {
                                 if let Trigger::MinDistance(min_dist) = swipe.trigger {
-                                    if min_dist <= SWIPE_DIRECTION_CHUNK_DIST * tracked_finger.swipe_chunk_count {
+                                    if min_dist <= config.chunk_dist.saturating_mul(tracked_finger.swipe_chunk_count) {
                                         // Reset swipe info
-                                        *tracked_finger = TrackedFinger::new(tracked_finger.last_pos);
+                                        tracked_finger.restart();
                                         // Swipe found
                                         potential_swipe = Some(swipe);
                                     }
@@ -162,14 +248,16 @@ impl SwipeTracker {
             },
             MultitouchEvent::Release { finger } => {
                 if let Some(tracked_finger) = self.trackings.remove(&finger.tracking_id) {
-                    if tracked_finger.swipe_chunk_count >= SWIPE_DIRECTION_COMPLETED_MIN_CHUNK_COUNT {
-                        if let Some(completed_direction) = tracked_finger.direction {
-                            for swipe in conditions.iter() {
-                                if swipe.direction == completed_direction && swipe.trigger == Trigger::Completed {
-                                    // Completed swipe detected
-                                    return Some(swipe);
-                                }
-                            }
+                    let completed = tracked_finger.swipe_chunk_count >= self.config.completed_min_chunk_count;
+                    let velocity = tracked_finger.release_velocity(now, &self.config);
+                    for swipe in conditions.iter() {
+                        match swipe.trigger {
+                            // Completed swipe detected
+                            Trigger::Completed if completed && tracked_finger.direction == Some(swipe.direction) => return Some(swipe),
+                            // Fast enough when released
+                            Trigger::Flick { min_velocity } if velocity >= min_velocity as f32
+                                && tracked_finger.flick_direction() == Some(swipe.direction) => return Some(swipe),
+                            _ => { }
                         }
                     }
                 }
@@ -180,4 +268,128 @@ impl SwipeTracker {
 
         None
     }
-}
\ No newline at end of file
+}
+
+/// Follows the fingers that drag the block sideways (ControlScheme::Drag).
+/// A finger only starts dragging once it moved SwipeConfig.chunk_dist
+/// horizontally, so taps with a bit of jitter don't move the block.
+pub struct DragTracker {
+    /// Where each finger was pressed and whether it is dragging already
+    fingers: FxHashMap<i32 /* Tracking id */, (u16 /* Start x */, bool)>,
+    config: SwipeConfig,
+}
+
+impl DragTracker {
+    pub fn new(config: SwipeConfig) -> Self {
+        Self { fingers: Default::default(), config }
+    }
+
+    pub fn set_config(&mut self, config: SwipeConfig) {
+        self.config = config;
+    }
+
+    /// Only fingers that got pressed with this can drag.
+    pub fn start(&mut self, tracking_id: i32, pos: Point2<u16>) {
+        self.fingers.insert(tracking_id, (pos.x, false));
+    }
+
+    /// The x coordinate to drag to if the finger is dragging.
+    pub fn detect(&mut self, event: MultitouchEvent) -> Option<u16> {
+        match event {
+            MultitouchEvent::Move { finger } => {
+                let (start_x, dragging) = self.fingers.get_mut(&finger.tracking_id)?;
+                *dragging |= start_x.abs_diff(finger.pos.x) >= self.config.chunk_dist;
+                if *dragging { Some(finger.pos.x) } else { None }
+            },
+            MultitouchEvent::Release { finger } => {
+                self.fingers.remove(&finger.tracking_id);
+                None
+            },
+            _ => None,
+        }
+    }
+}
+
+/// Recognizes tapping with several fingers at once. All of them
+/// have to be pressed within a short time and released without
+/// moving more than SwipeConfig.chunk_dist.
+pub struct MultiTapTracker {
+    finger_count: usize,
+    /// Where each finger currently down got pressed
+    fingers: FxHashMap<i32 /* Tracking id */, Point2<u16>>,
+    /// First press since no finger was down
+    first_press: Option<Instant>,
+    presses: usize,
+    /// A finger moved, came too late or too many got pressed
+    spoiled: bool,
+    config: SwipeConfig,
+}
+
+impl MultiTapTracker {
+    /// Time in which all fingers have to be pressed.
+    pub const PRESS_WINDOW: Duration = Duration::from_millis(300);
+
+    pub fn new(finger_count: usize, config: SwipeConfig) -> Self {
+        Self {
+            finger_count,
+            fingers: Default::default(),
+            first_press: None,
+            presses: 0,
+            spoiled: false,
+            config,
+        }
+    }
+
+    pub fn set_config(&mut self, config: SwipeConfig) {
+        self.config = config;
+    }
+
+    /// Forget all fingers, e.g. when releases might have been missed.
+    pub fn reset(&mut self) {
+        self.fingers.clear();
+        self.first_press = None;
+    }
+
+    pub fn detect(&mut self, event: MultitouchEvent) -> bool {
+        self.detect_at(event, Instant::now())
+    }
+
+    /// Same as detect() but with the time the event happened.
+    /// True once the last finger of a tap got released.
+    pub fn detect_at(&mut self, event: MultitouchEvent, now: Instant) -> bool {
+        match event {
+            MultitouchEvent::Press { finger } => {
+                let first_press = match self.first_press {
+                    Some(first_press) if !self.fingers.is_empty() => first_press,
+                    _ => {
+                        // A new tap starts
+                        self.presses = 0;
+                        self.spoiled = false;
+                        now
+                    },
+                };
+                self.first_press = Some(first_press);
+                self.presses += 1;
+                self.spoiled |= self.presses > self.finger_count
+                    || now.duration_since(first_press) > Self::PRESS_WINDOW;
+                self.fingers.insert(finger.tracking_id, finger.pos);
+            },
+            MultitouchEvent::Move { finger } => {
+                if let Some(start) = self.fingers.get(&finger.tracking_id) {
+                    let dist = std::cmp::max(start.x.abs_diff(finger.pos.x), start.y.abs_diff(finger.pos.y));
+                    self.spoiled |= dist >= self.config.chunk_dist;
+                }
+            },
+            MultitouchEvent::Release { finger } => {
+                // The tap ends with the last finger
+                let ended = self.fingers.remove(&finger.tracking_id).is_some() && self.fingers.is_empty();
+                return ended && !self.spoiled && self.presses == self.finger_count;
+            },
+            _ => { }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests;
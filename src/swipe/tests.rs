@@ -0,0 +1,198 @@
+//! Replays touch traces (see touch_trace.rs) against the SwipeTracker.
+//! The traces are synthetic, written in the format `--record-touch` writes.
+
+use super::*;
+use crate::touch_trace::{parse_trace, TraceEvent};
+
+const FLICK_DOWN: Swipe = Swipe { direction: Direction::Down, trigger: Trigger::Flick { min_velocity: 1000 } };
+const STEP_DOWN: Swipe = Swipe { direction: Direction::Down, trigger: Trigger::MinDistance(50) };
+const STEP_LEFT: Swipe = Swipe { direction: Direction::Left, trigger: Trigger::MinDistance(50) };
+const STEP_RIGHT: Swipe = Swipe { direction: Direction::Right, trigger: Trigger::MinDistance(50) };
+const COMPLETED_DOWN: Swipe = Swipe { direction: Direction::Down, trigger: Trigger::Completed };
+const COMPLETED_LEFT: Swipe = Swipe { direction: Direction::Left, trigger: Trigger::Completed };
+const COMPLETED_RIGHT: Swipe = Swipe { direction: Direction::Right, trigger: Trigger::Completed };
+
+/// The GameScene's down, left and right swipes. Its up swipes
+/// (half rotation and rotation) are left out.
+const GAME_SWIPES: [Swipe; 4] = [FLICK_DOWN, STEP_DOWN, STEP_LEFT, STEP_RIGHT];
+const COMPLETED_SWIPES: [Swipe; 3] = [COMPLETED_DOWN, COMPLETED_LEFT, COMPLETED_RIGHT];
+
+fn replay(trace: &str, config: SwipeConfig, conditions: &[Swipe]) -> Vec<Swipe> {
+    let trace = parse_trace(trace).expect("Invalid trace");
+    let start = Instant::now();
+    let mut tracker = SwipeTracker::new(config);
+    trace.into_iter()
+        .filter_map(|trace_event| tracker.detect_at(trace_event.event, conditions, start + trace_event.time).copied())
+        .collect()
+}
+
+#[test]
+fn trace_lines_roundtrip() {
+    let trace = parse_trace(include_str!("traces/left_drag.trace")).unwrap();
+    for trace_event in trace {
+        assert_eq!(TraceEvent::parse(&trace_event.to_line().unwrap()), Ok(trace_event));
+    }
+}
+
+#[test]
+fn trace_with_bad_line_is_rejected() {
+    assert!(parse_trace("0 press 1 10 10\n16 wiggle 1 20 10\n").is_err());
+    assert!(parse_trace("0 press 1 10\n").is_err());
+}
+
+#[test]
+fn tap_is_no_swipe() {
+    let trace = include_str!("traces/tap.trace");
+    assert_eq!(replay(trace, SwipeConfig::default(), &GAME_SWIPES), vec![]);
+    assert_eq!(replay(trace, SwipeConfig::default(), &COMPLETED_SWIPES), vec![]);
+}
+
+#[test]
+fn drag_fires_every_min_distance() {
+    let trace = include_str!("traces/left_drag.trace");
+    assert_eq!(replay(trace, SwipeConfig::default(), &GAME_SWIPES), vec![STEP_LEFT]);
+}
+
+#[test]
+fn drag_completes_on_release() {
+    let trace = include_str!("traces/left_drag.trace");
+    assert_eq!(replay(trace, SwipeConfig::default(), &COMPLETED_SWIPES), vec![COMPLETED_LEFT]);
+}
+
+#[test]
+fn shorter_chunks_fire_more_often() {
+    let trace = include_str!("traces/left_drag.trace");
+    let config = SwipeConfig { chunk_dist: 10, ..SwipeConfig::default() };
+    assert_eq!(replay(trace, config, &GAME_SWIPES), vec![STEP_LEFT; 2]);
+}
+
+#[test]
+fn slow_drag_is_no_flick() {
+    let trace = include_str!("traces/down_slow_drag.trace");
+    assert_eq!(replay(trace, SwipeConfig::default(), &GAME_SWIPES), vec![STEP_DOWN, STEP_DOWN]);
+}
+
+#[test]
+fn fast_flick_fires_on_release() {
+    let trace = include_str!("traces/down_flick.trace");
+    assert_eq!(replay(trace, SwipeConfig::default(), &GAME_SWIPES), vec![STEP_DOWN, STEP_DOWN, FLICK_DOWN]);
+}
+
+#[test]
+fn flick_released_right_after_a_step_fires() {
+    let trace = include_str!("traces/down_flick_release_after_step.trace");
+    assert_eq!(replay(trace, SwipeConfig::default(), &GAME_SWIPES), vec![STEP_DOWN, STEP_DOWN, STEP_DOWN, FLICK_DOWN]);
+    let trace = include_str!("traces/down_short_flick.trace");
+    assert_eq!(replay(trace, SwipeConfig::default(), &GAME_SWIPES), vec![STEP_DOWN, FLICK_DOWN]);
+}
+
+#[test]
+fn resting_before_release_is_no_flick() {
+    let trace = include_str!("traces/down_flick_then_rest.trace");
+    assert_eq!(replay(trace, SwipeConfig::default(), &GAME_SWIPES), vec![STEP_DOWN, STEP_DOWN]);
+}
+
+#[test]
+fn instant_moves_count_as_flick() {
+    // Same events, but all at the same time. Must not
+    // break the velocity calculation.
+    let trace = include_str!("traces/down_flick.trace");
+    let trace = parse_trace(trace).unwrap();
+    let now = Instant::now();
+    let mut tracker = SwipeTracker::new(SwipeConfig::default());
+    let swipes: Vec<Swipe> = trace.into_iter()
+        .filter_map(|trace_event| tracker.detect_at(trace_event.event, &GAME_SWIPES, now).copied())
+        .collect();
+    assert_eq!(swipes.last(), Some(&FLICK_DOWN));
+}
+
+#[test]
+fn direction_change_starts_new_swipe() {
+    let trace = include_str!("traces/right_then_down.trace");
+    assert_eq!(replay(trace, SwipeConfig::default(), &COMPLETED_SWIPES), vec![COMPLETED_DOWN]);
+    assert_eq!(replay(trace, SwipeConfig::default(), &GAME_SWIPES), vec![STEP_RIGHT, STEP_DOWN]);
+}
+
+#[test]
+fn direction_change_invalidates_without_multiple_swipes() {
+    let trace = include_str!("traces/right_then_down.trace");
+    let config = SwipeConfig { allow_multiple_swipes_at_once: false, ..SwipeConfig::default() };
+    assert_eq!(replay(trace, config, &COMPLETED_SWIPES), vec![]);
+    // A swipe that already fired starts fresh
+    assert_eq!(replay(trace, config, &GAME_SWIPES), vec![STEP_RIGHT, STEP_DOWN]);
+}
+
+#[test]
+fn fingers_are_tracked_separately() {
+    let trace = include_str!("traces/two_fingers_apart.trace");
+    let swipes = replay(trace, SwipeConfig::default(), &COMPLETED_SWIPES);
+    assert_eq!(swipes.len(), 2);
+    assert!(swipes.contains(&COMPLETED_LEFT));
+    assert!(swipes.contains(&COMPLETED_RIGHT));
+}
+
+/// Every x a dragging finger moves the block to.
+fn replay_drag(trace: &str, config: SwipeConfig) -> Vec<u16> {
+    let trace = parse_trace(trace).expect("Invalid trace");
+    let mut tracker = DragTracker::new(config);
+    trace.into_iter()
+        .filter_map(|trace_event| {
+            if let MultitouchEvent::Press { finger } = trace_event.event {
+                tracker.start(finger.tracking_id, finger.pos);
+            }
+            tracker.detect(trace_event.event)
+        })
+        .collect()
+}
+
+#[test]
+fn jittery_tap_doesnt_drag() {
+    assert_eq!(replay_drag(include_str!("traces/tap.trace"), SwipeConfig::default()), vec![]);
+    assert_eq!(replay_drag(include_str!("traces/jittery_tap.trace"), SwipeConfig::default()), vec![]);
+}
+
+#[test]
+fn drag_starts_after_chunk_distance() {
+    let xs = replay_drag(include_str!("traces/left_drag.trace"), SwipeConfig::default());
+    // 25 px away from 800
+    assert_eq!(xs.first(), Some(&770));
+    assert_eq!(xs.last(), Some(&650));
+}
+
+#[test]
+fn fingers_not_started_dont_drag() {
+    let trace = parse_trace(include_str!("traces/left_drag.trace")).unwrap();
+    let mut tracker = DragTracker::new(SwipeConfig::default());
+    assert!(trace.into_iter().all(|trace_event| tracker.detect(trace_event.event).is_none()));
+}
+
+/// How often three fingers tapped.
+fn replay_three_finger_taps(trace: &str) -> usize {
+    let trace = parse_trace(trace).expect("Invalid trace");
+    let start = Instant::now();
+    let mut tracker = MultiTapTracker::new(3, SwipeConfig::default());
+    trace.into_iter()
+        .filter(|trace_event| tracker.detect_at(trace_event.event, start + trace_event.time))
+        .count()
+}
+
+#[test]
+fn three_fingers_tap_once() {
+    assert_eq!(replay_three_finger_taps(include_str!("traces/three_finger_tap.trace")), 1);
+}
+
+#[test]
+fn swiping_while_fingers_rest_is_no_tap() {
+    assert_eq!(replay_three_finger_taps(include_str!("traces/three_fingers_one_swipes.trace")), 0);
+}
+
+#[test]
+fn slow_presses_are_no_tap() {
+    assert_eq!(replay_three_finger_taps(include_str!("traces/three_fingers_slowly.trace")), 0);
+}
+
+#[test]
+fn taps_with_one_finger_dont_add_up() {
+    let trace = "0 press 1 500 800\n30 release 1 500 800\n60 press 2 500 800\n90 release 2 500 800\n120 press 3 500 800\n150 release 3 500 800\n";
+    assert_eq!(replay_three_finger_taps(trace), 0);
+}
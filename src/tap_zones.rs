@@ -0,0 +1,86 @@
+//! Screen areas that perform an action when tapped
+//! (ControlScheme::TapZones).
+//!
+//! ```text
+//! +------+--------+-------+
+//! |      |        |       |
+//! | Left | Rotate | Right |
+//! |      |        |       |
+//! +------+--------+-------+
+//! |         Drop          |
+//! +-----------------------+
+//! ```
+
+use crate::canvas::{mxcfb_rect, DISPLAYHEIGHT, DISPLAYWIDTH};
+use libremarkable::cgmath::Point2;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TapZonesConfig {
+    /// Part of the screen width used by the left
+    /// and right zone each (0.0 - 0.5).
+    pub side_width: f32,
+    /// Part of the screen height at the bottom
+    /// used by the drop zone (0.0 - 1.0).
+    pub drop_height: f32,
+    /// Faintly draw the borders of the zones
+    /// when the game starts.
+    pub show_outlines: bool,
+}
+
+impl Default for TapZonesConfig {
+    fn default() -> Self {
+        Self {
+            side_width: 1.0 / 3.0,
+            drop_height: 0.15,
+            show_outlines: true,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TapZone { Left, Right, Rotate, Drop }
+
+impl TapZonesConfig {
+    fn side_width_px(&self) -> u32 {
+        (DISPLAYWIDTH as f32 * self.side_width.clamp(0.0, 0.5)) as u32
+    }
+
+    fn drop_top_px(&self) -> u32 {
+        (DISPLAYHEIGHT as f32 * (1.0 - self.drop_height.clamp(0.0, 1.0))) as u32
+    }
+
+    pub fn zone_at(&self, pos: Point2<u16>) -> TapZone {
+        let x = pos.x as u32;
+        if pos.y as u32 >= self.drop_top_px() {
+            TapZone::Drop
+        }else if x < self.side_width_px() {
+            TapZone::Left
+        }else if x >= DISPLAYWIDTH as u32 - self.side_width_px() {
+            TapZone::Right
+        }else {
+            TapZone::Rotate
+        }
+    }
+
+    /// The lines between the zones (as thin rects). Zones
+    /// that are turned off (size 0) get no border.
+    pub fn borders(&self, thickness: u32) -> Vec<mxcfb_rect> {
+        let drop_top = self.drop_top_px();
+        let side_width = self.side_width_px();
+        let mut borders = vec![];
+        if drop_top < DISPLAYHEIGHT as u32 {
+            // Stays on the screen even if the drop zone is thinner
+            let top = drop_top.min(DISPLAYHEIGHT as u32 - thickness);
+            borders.push(mxcfb_rect { left: 0, top, width: DISPLAYWIDTH as u32, height: thickness });
+        }
+        if side_width > 0 && drop_top > 0 {
+            for left in [side_width, DISPLAYWIDTH as u32 - side_width] {
+                borders.push(mxcfb_rect { left, top: 0, width: thickness, height: drop_top });
+            }
+        }
+        borders
+    }
+}
+
+#[cfg(test)]
+mod tests;
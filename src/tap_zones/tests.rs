@@ -0,0 +1,65 @@
+use super::*;
+
+fn at(x: u32, y: u32) -> Point2<u16> {
+    Point2 { x: x as u16, y: y as u16 }
+}
+
+const BOTTOM: u32 = DISPLAYHEIGHT as u32 - 1;
+const RIGHT: u32 = DISPLAYWIDTH as u32 - 1;
+const CENTER_X: u32 = DISPLAYWIDTH as u32 / 2;
+
+fn zones(side_width: f32, drop_height: f32) -> TapZonesConfig {
+    TapZonesConfig { side_width, drop_height, show_outlines: true }
+}
+
+#[test]
+fn default_layout() {
+    let zones = TapZonesConfig::default();
+    assert_eq!(zones.zone_at(at(0, 0)), TapZone::Left);
+    assert_eq!(zones.zone_at(at(CENTER_X, 0)), TapZone::Rotate);
+    assert_eq!(zones.zone_at(at(RIGHT, 0)), TapZone::Right);
+    assert_eq!(zones.zone_at(at(0, BOTTOM)), TapZone::Drop);
+    assert_eq!(zones.zone_at(at(RIGHT, BOTTOM)), TapZone::Drop);
+    assert_eq!(zones.borders(2).len(), 3);
+}
+
+#[test]
+fn without_side_zones() {
+    let zones = zones(0.0, 0.15);
+    assert_eq!(zones.zone_at(at(0, 0)), TapZone::Rotate);
+    assert_eq!(zones.zone_at(at(RIGHT, 0)), TapZone::Rotate);
+    assert_eq!(zones.zone_at(at(0, BOTTOM)), TapZone::Drop);
+    assert_eq!(zones.borders(2).len(), 1);
+}
+
+#[test]
+fn halves_without_rotate_zone() {
+    let zones = zones(0.5, 0.15);
+    assert_eq!(zones.zone_at(at(CENTER_X - 1, 0)), TapZone::Left);
+    assert_eq!(zones.zone_at(at(CENTER_X, 0)), TapZone::Right);
+}
+
+#[test]
+fn without_drop_zone() {
+    let zones = zones(1.0 / 3.0, 0.0);
+    assert_eq!(zones.zone_at(at(0, BOTTOM)), TapZone::Left);
+    assert_eq!(zones.zone_at(at(CENTER_X, BOTTOM)), TapZone::Rotate);
+    let borders = zones.borders(2);
+    // Only the side borders, over the whole height
+    assert_eq!(borders.len(), 2);
+    assert!(borders.iter().all(|border| border.height == DISPLAYHEIGHT as u32));
+}
+
+#[test]
+fn thin_drop_zone_border_stays_on_screen() {
+    let zones = zones(1.0 / 3.0, 0.0001);
+    let borders = zones.borders(4);
+    assert!(borders.iter().all(|border| border.top + border.height <= DISPLAYHEIGHT as u32));
+}
+
+#[test]
+fn out_of_range_values_are_clamped() {
+    let zones = zones(2.0, 1.5);
+    assert_eq!(zones.zone_at(at(0, 0)), TapZone::Drop);
+    assert!(zones.borders(2).iter().all(|border| border.width > 0 && border.height > 0));
+}
@@ -0,0 +1,128 @@
+//! Recording and replaying of touch input.
+//!
+//! A trace is a text file with one multitouch event per line:
+//!
+//! ```text
+//! # Comment
+//! <ms since start> <press|move|release> <tracking id> <x> <y>
+//! ```
+//!
+//! Traces can be recorded on the device with `--record-touch <FILE>`,
+//! replayed into the game with `--replay-touch <FILE>` and are
+//! replayed against the `SwipeTracker` in the tests.
+//!
+//! The traces in `src/swipe/traces` are synthetic: written by hand
+//! to cover specific gestures, not recorded on a device.
+
+use libremarkable::cgmath::Point2;
+use libremarkable::input::{Finger, InputEvent, MultitouchEvent};
+use std::fmt::Display;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TraceEvent {
+    /// Time since the start of the trace
+    pub time: Duration,
+    pub event: MultitouchEvent,
+}
+
+impl TraceEvent {
+    pub fn to_line(self) -> Option<String> {
+        let (kind, finger) = match self.event {
+            MultitouchEvent::Press { finger } => ("press", finger),
+            MultitouchEvent::Move { finger } => ("move", finger),
+            MultitouchEvent::Release { finger } => ("release", finger),
+            MultitouchEvent::Unknown => return None,
+        };
+        Some(format!("{} {} {} {} {}", self.time.as_millis(), kind, finger.tracking_id, finger.pos.x, finger.pos.y))
+    }
+
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        if parts.len() != 5 {
+            return Err(format!("Expected 5 values but got {}", parts.len()));
+        }
+        let mut finger = Finger::default();
+        finger.tracking_id = parse_part(parts[2], "tracking id")?;
+        finger.pos = Point2 { x: parse_part(parts[3], "x")?, y: parse_part(parts[4], "y")? };
+        let event = match parts[1] {
+            "press" => {
+                finger.pressed = true;
+                MultitouchEvent::Press { finger }
+            },
+            "move" => {
+                finger.pressed = true;
+                MultitouchEvent::Move { finger }
+            },
+            "release" => MultitouchEvent::Release { finger },
+            kind => return Err(format!("Unknown event \"{}\"", kind)),
+        };
+        Ok(Self { time: Duration::from_millis(parse_part(parts[0], "time")?), event })
+    }
+}
+
+fn parse_part<T: FromStr>(part: &str, name: &str) -> Result<T, String> where T::Err: Display {
+    part.parse::<T>().map_err(|err| format!("Invalid {} \"{}\": {}", name, part, err))
+}
+
+/// Parses a whole trace. Empty lines and comments are skipped.
+pub fn parse_trace(contents: &str) -> Result<Vec<TraceEvent>, String> {
+    contents.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(|(line_number, line)| TraceEvent::parse(line).map_err(|err| format!("Line {}: {}", line_number + 1, err)))
+        .collect()
+}
+
+/// Writes all touch events into a trace file.
+pub struct TraceRecorder {
+    writer: BufWriter<File>,
+    start: Instant,
+}
+
+impl TraceRecorder {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "# reTris touch trace")?;
+        Ok(Self { writer, start: Instant::now() })
+    }
+
+    pub fn record(&mut self, event: MultitouchEvent) {
+        let trace_event = TraceEvent { time: self.start.elapsed(), event };
+        if let Some(line) = trace_event.to_line() {
+            // Flushing every line so nothing is lost when killed
+            if let Err(err) = writeln!(self.writer, "{}", line).and_then(|_| self.writer.flush()) {
+                eprintln!("Failed to record touch event: {}", err);
+            }
+        }
+    }
+}
+
+/// Feeds a recorded trace with its original timing into
+/// the input channel as if the touches happened right now.
+pub fn replay(path: &Path, input_tx: Sender<InputEvent>) -> Result<(), String> {
+    let contents = fs::read_to_string(path).map_err(|err| err.to_string())?;
+    let trace = parse_trace(&contents)?;
+    thread::spawn(move || {
+        let start = Instant::now();
+        for trace_event in trace {
+            let elapsed = start.elapsed();
+            if trace_event.time > elapsed {
+                thread::sleep(trace_event.time - elapsed);
+            }
+            if input_tx.send(InputEvent::MultitouchEvent { event: trace_event.event }).is_err() {
+                break;
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,23 @@
+use super::*;
+
+#[test]
+fn lines_survive_a_round_trip() {
+    let line = "1234 move 7 1403 1871";
+    assert_eq!(TraceEvent::parse(line).unwrap().to_line().as_deref(), Some(line));
+}
+
+#[test]
+fn values_out_of_range_are_rejected() {
+    let lines = [
+        "0 press 1 70000 100",
+        "0 press 1 100 -5",
+        "0 press 1 1.5 100",
+        "0 press 3000000000 100 100",
+        "-1 press 1 100 100",
+        "0 hover 1 100 100",
+        "0 press 1 100",
+    ];
+    for line in lines.iter() {
+        assert!(TraceEvent::parse(line).is_err(), "{:?} was accepted", line);
+    }
+}
@@ -0,0 +1,265 @@
+//! Versus play between two devices over TCP.
+//!
+//! The protocol is line based plain text so it can be debugged
+//! with e.g. `nc` from a computer. Every line is one message:
+//!
+//! ```text
+//! HELLO <protocol version>
+//! BOARD <width> <height> <cells>   cells: row by row, '#' = filled, '.' = empty
+//! GARBAGE <lines>
+//! GAMEOVER <score>
+//! ```
+
+use std::fmt::Display;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs, UdpSocket};
+use std::str::FromStr;
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+pub const DEFAULT_PORT: u16 = 7474;
+pub const PROTOCOL_VERSION: u32 = 1;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Bounds how long dropping a connection waits for a stalled other device
+const WRITE_TIMEOUT: Duration = Duration::from_secs(2);
+/// Largest board accepted from the other device (the size of every game).
+pub const MAX_BOARD_WIDTH: usize = 10;
+pub const MAX_BOARD_HEIGHT: usize = 22;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum VersusOutcome {
+    Won,
+    Lost,
+    OpponentLeft,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    Hello { version: u32 },
+    Board { width: usize, height: usize, cells: Vec<bool> },
+    Garbage { lines: u32 },
+    GameOver { score: u64 },
+}
+
+impl Message {
+    pub fn to_line(&self) -> String {
+        match self {
+            Message::Hello { version } => format!("HELLO {}", version),
+            Message::Board { width, height, cells } => {
+                let cells: String = cells.iter().map(|filled| if *filled { '#' } else { '.' }).collect();
+                format!("BOARD {} {} {}", width, height, cells)
+            },
+            Message::Garbage { lines } => format!("GARBAGE {}", lines),
+            Message::GameOver { score } => format!("GAMEOVER {}", score),
+        }
+    }
+
+    pub fn parse(line: &str) -> Result<Message, String> {
+        let mut parts = line.split_whitespace();
+        let command = parts.next().ok_or("Empty message")?;
+
+        let message = match command {
+            "HELLO" => Message::Hello { version: parse_part(parts.next(), command, "version")? },
+            "GARBAGE" => Message::Garbage { lines: parse_part(parts.next(), command, "lines")? },
+            "GAMEOVER" => Message::GameOver { score: parse_part(parts.next(), command, "score")? },
+            "BOARD" => {
+                let width: usize = parse_part(parts.next(), command, "width")?;
+                let height: usize = parse_part(parts.next(), command, "height")?;
+                if width == 0 || width > MAX_BOARD_WIDTH || height == 0 || height > MAX_BOARD_HEIGHT {
+                    return Err(format!("BOARD has unsupported size {}x{}", width, height));
+                }
+                let expected = width.checked_mul(height).ok_or("BOARD is too large")?;
+                let cells = parts.next().ok_or("BOARD is missing cells")?;
+                if cells.len() != expected || cells.chars().any(|c| c != '#' && c != '.') {
+                    return Err(format!("BOARD needs {} cells of '#' or '.'", expected));
+                }
+                Message::Board { width, height, cells: cells.chars().map(|c| c == '#').collect() }
+            },
+            _ => return Err(format!("Unknown message: {}", command)),
+        };
+        if parts.next().is_some() {
+            return Err(format!("{} has too many parts", command));
+        }
+        Ok(message)
+    }
+}
+
+fn parse_part<T: FromStr>(part: Option<&str>, command: &str, name: &str) -> Result<T, String> where T::Err: Display {
+    part.ok_or(format!("{} is missing {}", command, name))?
+        .parse::<T>()
+        .map_err(|err| format!("{} has invalid {}: {}", command, name, err))
+}
+
+/// An established connection to the other device.
+///
+/// Incoming messages are read and outgoing ones written by
+/// background threads, so a slow or stalled other device
+/// never blocks the game.
+pub struct Connection {
+    stream: TcpStream,
+    incoming: Receiver<Message>,
+    /// Only taken when dropping
+    outgoing: Option<Sender<Message>>,
+    writer: Option<JoinHandle<()>>,
+    disconnected: bool,
+}
+
+impl Connection {
+    fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nodelay(true)?;
+        stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+        let reader = BufReader::new(stream.try_clone()?);
+        let (incoming_tx, incoming) = channel::<Message>();
+        thread::spawn(move || {
+            for line in reader.lines() {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(_) => break,
+                };
+                if line.trim().is_empty() {
+                    continue;
+                }
+                match Message::parse(&line) {
+                    Ok(message) => {
+                        if incoming_tx.send(message).is_err() {
+                            break; // Connection got dropped
+                        }
+                    },
+                    Err(err) => eprintln!("Versus: Ignoring bad message ({})", err),
+                }
+            }
+        });
+
+        let mut writer = BufWriter::new(stream.try_clone()?);
+        let (outgoing, outgoing_rx) = channel::<Message>();
+        let writer = thread::spawn(move || {
+            // Ends when the connection got dropped or writing failed,
+            // which makes send() notice it
+            for message in outgoing_rx {
+                if writeln!(writer, "{}", message.to_line()).and_then(|_| writer.flush()).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut connection = Self {
+            stream,
+            incoming,
+            outgoing: Some(outgoing),
+            writer: Some(writer),
+            disconnected: false,
+        };
+        connection.send(&Message::Hello { version: PROTOCOL_VERSION });
+        Ok(connection)
+    }
+
+    pub fn send(&mut self, message: &Message) {
+        if self.disconnected {
+            return;
+        }
+        let sent = self.outgoing.as_ref().is_some_and(|outgoing| outgoing.send(message.clone()).is_ok());
+        if !sent {
+            self.disconnected = true;
+        }
+    }
+
+    /// Returns the next received message if any.
+    pub fn try_recv(&mut self) -> Option<Message> {
+        match self.incoming.try_recv() {
+            Ok(message) => Some(message),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => {
+                self.disconnected = true;
+                None
+            },
+        }
+    }
+
+    /// Whether the other side closed the connection.
+    pub fn is_disconnected(&self) -> bool {
+        self.disconnected
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        // Let the writer send everything still queued (like a GameOver)
+        // before closing, otherwise the other device might never get it
+        self.outgoing.take();
+        if let Some(writer) = self.writer.take() {
+            writer.join().ok();
+        }
+        // The reader ends once the other side closes as well
+        self.stream.shutdown(std::net::Shutdown::Write).ok();
+    }
+}
+
+/// A connection that is about to be established.
+/// Needs to be polled until it yields a result.
+pub enum PendingConnection {
+    Hosting(TcpListener),
+    Joining(Receiver<io::Result<TcpStream>>),
+}
+
+impl PendingConnection {
+    /// Wait for another device to join on the given port.
+    pub fn host(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        listener.set_nonblocking(true)?;
+        Ok(PendingConnection::Hosting(listener))
+    }
+
+    /// Connect to a hosting device. The default port is used
+    /// if `addr` doesn't contain one.
+    pub fn join(addr: &str) -> Self {
+        let addr = if addr.contains(':') { addr.to_owned() } else { format!("{}:{}", addr, DEFAULT_PORT) };
+        let (result_tx, result_rx) = channel::<io::Result<TcpStream>>();
+        thread::spawn(move || {
+            let result = addr.to_socket_addrs().and_then(|mut addrs| {
+                let addr = addrs.next().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Address not found"))?;
+                TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT)
+            });
+            result_tx.send(result).ok();
+        });
+        PendingConnection::Joining(result_rx)
+    }
+
+    pub fn poll(&mut self) -> Option<io::Result<Connection>> {
+        let stream = match self {
+            PendingConnection::Hosting(listener) => match listener.accept() {
+                Ok((stream, _)) => stream.set_nonblocking(false).map(|_| stream),
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => return None,
+                Err(err) => Err(err),
+            },
+            PendingConnection::Joining(result_rx) => match result_rx.try_recv() {
+                Ok(result) => result,
+                Err(TryRecvError::Empty) => return None,
+                Err(TryRecvError::Disconnected) => Err(io::Error::other("Connecting failed")),
+            },
+        };
+        Some(stream.and_then(Connection::new))
+    }
+}
+
+/// Best guess of the ip address other devices can use to reach this one.
+/// No packets are sent for this.
+pub fn local_ip() -> Option<String> {
+    let socket = UdpSocket::bind("0.0.0.0:0").ok()?;
+    socket.connect("10.255.255.255:1").ok()?;
+    Some(socket.local_addr().ok()?.ip().to_string())
+}
+
+/// Amount of garbage lines to send to the opponent for clearing
+/// `lines` at once.
+pub fn garbage_for_lines(lines: u32) -> u32 {
+    match lines {
+        0 | 1 => 0,
+        2 => 1,
+        3 => 2,
+        _ => 4,
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,111 @@
+use super::*;
+use std::time::Instant;
+
+#[test]
+fn messages_survive_a_round_trip() {
+    let messages = [
+        Message::Hello { version: PROTOCOL_VERSION },
+        Message::Board { width: 2, height: 3, cells: vec![true, false, false, false, true, true] },
+        Message::Garbage { lines: 4 },
+        Message::GameOver { score: 12300 },
+    ];
+    for message in messages.iter() {
+        assert_eq!(Message::parse(&message.to_line()).as_ref(), Ok(message));
+    }
+    assert_eq!(Message::Board { width: 2, height: 1, cells: vec![true, false] }.to_line(), "BOARD 2 1 #.");
+}
+
+#[test]
+fn malformed_messages_are_rejected() {
+    let lines = [
+        "",
+        "HI 1",
+        "HELLO",
+        "HELLO one",
+        "HELLO -1",
+        "HELLO 1 2",
+        "GARBAGE 99999999999",
+        "GAMEOVER",
+        "BOARD 2 2 ###",
+        "BOARD 2 2 ##x#",
+        "BOARD 2 2",
+        "BOARD 0 0 ",
+        "BOARD 11 22 #",
+        "BOARD 10 23 #",
+        "BOARD 18446744073709551615 18446744073709551615 #",
+    ];
+    for line in lines.iter() {
+        assert!(Message::parse(line).is_err(), "{:?} was accepted", line);
+    }
+}
+
+fn poll_until_connected(pending: &mut PendingConnection) -> Connection {
+    let start = Instant::now();
+    loop {
+        if let Some(result) = pending.poll() {
+            return result.expect("Connecting failed");
+        }
+        assert!(start.elapsed() < Duration::from_secs(5), "Connecting timed out");
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+fn recv(connection: &mut Connection) -> Message {
+    let start = Instant::now();
+    loop {
+        if let Some(message) = connection.try_recv() {
+            return message;
+        }
+        assert!(start.elapsed() < Duration::from_secs(5), "Receiving timed out");
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn connections_exchange_messages_on_localhost() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.set_nonblocking(true).unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let mut joining = PendingConnection::join(&addr);
+    let mut hosting = PendingConnection::Hosting(listener);
+    let mut host = poll_until_connected(&mut hosting);
+    let mut guest = poll_until_connected(&mut joining);
+
+    assert_eq!(recv(&mut host), Message::Hello { version: PROTOCOL_VERSION });
+    assert_eq!(recv(&mut guest), Message::Hello { version: PROTOCOL_VERSION });
+
+    let board = Message::Board { width: 10, height: 22, cells: (0..220).map(|i| i % 3 == 0).collect() };
+    host.send(&board);
+    host.send(&Message::Garbage { lines: 2 });
+    assert_eq!(recv(&mut guest), board);
+    assert_eq!(recv(&mut guest), Message::Garbage { lines: 2 });
+
+    guest.send(&Message::GameOver { score: 500 });
+    assert_eq!(recv(&mut host), Message::GameOver { score: 500 });
+
+    drop(guest);
+    let start = Instant::now();
+    while !host.is_disconnected() {
+        assert_eq!(host.try_recv(), None);
+        assert!(start.elapsed() < Duration::from_secs(5), "Disconnect not noticed");
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn messages_sent_right_before_dropping_arrive() {
+    for _ in 0..20 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let mut joining = PendingConnection::join(&addr);
+        let mut hosting = PendingConnection::Hosting(listener);
+        let mut host = poll_until_connected(&mut hosting);
+        let mut guest = poll_until_connected(&mut joining);
+
+        host.send(&Message::GameOver { score: 1200 });
+        drop(host);
+        assert_eq!(recv(&mut guest), Message::Hello { version: PROTOCOL_VERSION });
+        assert_eq!(recv(&mut guest), Message::GameOver { score: 1200 });
+    }
+}
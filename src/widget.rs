@@ -0,0 +1,649 @@
+//! Buttons and other widgets for menus and the HUD.
+//!
+//! Widgets know their size before they get drawn, so they can be placed
+//! with row() and column(). Instead of keeping a hitbox and a flag per
+//! button, scenes give each widget an action and ask which one was hit.
+//!
+//! Buttons can also follow the finger themselves (on_touch()). They get
+//! inverted while pressed, so a press shows up right away even on a slow
+//! display, and only trigger when released on them.
+//!
+//! Menus can be used with the hardware buttons as well: LEFT and RIGHT
+//! move a frame around the focused button and MIDDLE activates it.
+
+use crate::canvas::*;
+use libremarkable::input::{GPIOEvent, InputEvent, MultitouchEvent, PhysicalButton};
+
+/// Space between a button and the frame around it when focused.
+const FOCUS_MARGIN: u32 = 10;
+
+pub trait Widget {
+    /// Size when drawn. Known before drawing.
+    fn size(&self) -> Vector2<u32>;
+    /// Moves the top left corner.
+    fn set_pos(&mut self, pos: Point2<i32>);
+    fn draw(&mut self, canvas: &mut Canvas) -> mxcfb_rect;
+}
+
+fn rect_at(pos: Point2<i32>, size: Vector2<u32>) -> mxcfb_rect {
+    mxcfb_rect { left: pos.x.max(0) as u32, top: pos.y.max(0) as u32, width: size.x, height: size.y }
+}
+
+/// Places the widgets next to each other. The whole row
+/// is centered on the screen if no x is given.
+pub fn row(widgets: &mut [&mut dyn Widget], x: Option<i32>, top: i32, gap: u32) {
+    let width = widgets.iter().map(|widget| widget.size().x).sum::<u32>() + gap * widgets.len().saturating_sub(1) as u32;
+    let mut x = x.unwrap_or(DISPLAYWIDTH as i32 / 2 - width as i32 / 2);
+    for widget in widgets.iter_mut() {
+        widget.set_pos(Point2 { x, y: top });
+        x += (widget.size().x + gap) as i32;
+    }
+}
+
+/// Places the widgets below each other. Each one is
+/// centered on the screen if no x is given.
+pub fn column(widgets: &mut [&mut dyn Widget], x: Option<i32>, top: i32, gap: u32) {
+    let mut y = top;
+    for widget in widgets.iter_mut() {
+        let size = widget.size();
+        widget.set_pos(Point2 { x: x.unwrap_or(DISPLAYWIDTH as i32 / 2 - size.x as i32 / 2), y });
+        y += (size.y + gap) as i32;
+    }
+}
+
+/// Draws all widgets and returns the area of all of them.
+pub fn draw_all(widgets: &mut [&mut dyn Widget], canvas: &mut Canvas) -> Option<mxcfb_rect> {
+    widgets.iter_mut()
+        .map(|widget| widget.draw(canvas))
+        .reduce(|all, rect| all.merge_rect(&rect))
+}
+
+/// Widgets that can be selected with the hardware buttons.
+pub trait Focusable {
+    /// Whether it is on the screen and enabled.
+    fn can_focus(&self) -> bool;
+    fn is_focused(&self) -> bool;
+    fn set_focused(&mut self, focused: bool);
+}
+
+/// What a hardware button does in menus.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Navigation {
+    /// Focus the next (1) or previous (-1) widget
+    Move(i32),
+    /// Activate the focused widget
+    Activate,
+}
+
+pub fn navigation(event: &InputEvent) -> Option<Navigation> {
+    match *event {
+        InputEvent::GPIO { event: GPIOEvent::Press { button: PhysicalButton::LEFT } } => Some(Navigation::Move(-1)),
+        InputEvent::GPIO { event: GPIOEvent::Press { button: PhysicalButton::RIGHT } } => Some(Navigation::Move(1)),
+        InputEvent::GPIO { event: GPIOEvent::Press { button: PhysicalButton::MIDDLE } } => Some(Navigation::Activate),
+        _ => None,
+    }
+}
+
+/// Focuses the next (1) or previous (-1) widget that can take it and
+/// wraps around. Starts at the first or last one if none is focused.
+pub fn move_focus(widgets: &mut [&mut dyn Focusable], step: i32) {
+    let count = widgets.len() as i32;
+    let current = widgets.iter().position(|widget| widget.is_focused());
+    let start = match current {
+        Some(index) => index as i32,
+        None if step > 0 => -1,
+        None => count,
+    };
+    for offset in 1..=count {
+        let index = (start + step * offset).rem_euclid(count) as usize;
+        if widgets[index].can_focus() {
+            if let Some(current) = current {
+                widgets[current].set_focused(false);
+            }
+            widgets[index].set_focused(true);
+            return;
+        }
+    }
+}
+
+/// A single line of text.
+pub struct Label {
+    text: String,
+    font_size: f32,
+    pos: Point2<i32>,
+}
+
+impl Label {
+    pub fn new(text: &str, font_size: f32) -> Self {
+        Self { text: text.to_owned(), font_size, pos: Point2 { x: 0, y: 0 } }
+    }
+
+    pub fn set_text(&mut self, text: &str) {
+        self.text = text.to_owned();
+    }
+
+    pub fn rect(&self) -> mxcfb_rect {
+        rect_at(self.pos, self.size())
+    }
+}
+
+impl Widget for Label {
+    fn size(&self) -> Vector2<u32> {
+        measure_text(&self.text, self.font_size)
+    }
+
+    fn set_pos(&mut self, pos: Point2<i32>) {
+        self.pos = pos;
+    }
+
+    fn draw(&mut self, canvas: &mut Canvas) -> mxcfb_rect {
+        let baseline = self.pos.y + ascent(self.font_size) as i32;
+        canvas.draw_text(Point2 { x: Some(self.pos.x), y: Some(baseline) }, &self.text, self.font_size);
+        self.rect()
+    }
+}
+
+/// Text with a border around it that reports its action when hit.
+/// Disabled buttons get a thin border and can't be hit.
+pub struct Button<A> {
+    label: Label,
+    vgap: u32,
+    hgap: u32,
+    action: A,
+    enabled: bool,
+    /// Only buttons that are on the screen can be hit
+    shown: bool,
+    pos: Point2<i32>,
+    /// Tracking id of the finger that is pressing it
+    pressed_by: Option<i32>,
+    focused: bool,
+    /// Pressed or focused state changed since the last draw
+    feedback_changed: bool,
+}
+
+impl<A: Copy> Button<A> {
+    pub fn new(text: &str, font_size: f32, action: A) -> Self {
+        Self {
+            label: Label::new(text, font_size),
+            vgap: 25,
+            hgap: 50,
+            action,
+            enabled: true,
+            shown: false,
+            pos: Point2 { x: 0, y: 0 },
+            pressed_by: None,
+            focused: false,
+            feedback_changed: false,
+        }
+    }
+
+    /// Space between the text and the border.
+    pub fn with_gaps(mut self, vgap: u32, hgap: u32) -> Self {
+        self.vgap = vgap;
+        self.hgap = hgap;
+        self
+    }
+
+    pub fn set_text(&mut self, text: &str) {
+        self.label.set_text(text);
+        self.set_pos(self.pos);
+    }
+
+    pub fn action(&self) -> A {
+        self.action
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    pub fn is_shown(&self) -> bool {
+        self.shown
+    }
+
+    /// Can't be hit anymore until it gets drawn again.
+    pub fn hide(&mut self) {
+        self.shown = false;
+        self.pressed_by = None;
+        self.focused = false;
+    }
+
+    pub fn rect(&self) -> mxcfb_rect {
+        rect_at(self.pos, self.size())
+    }
+
+    /// Area including the frame shown when focused.
+    fn outer_rect(&self) -> mxcfb_rect {
+        let size = self.size();
+        rect_at(
+            Point2 { x: self.pos.x - FOCUS_MARGIN as i32, y: self.pos.y - FOCUS_MARGIN as i32 },
+            Vector2 { x: size.x + 2 * FOCUS_MARGIN, y: size.y + 2 * FOCUS_MARGIN }
+        )
+    }
+
+    /// The action if the button is focused (see Navigation::Activate).
+    pub fn activate(&self) -> Option<A> {
+        if self.focused && self.can_focus() {
+            Some(self.action)
+        }else {
+            None
+        }
+    }
+
+    fn set_pressed_by(&mut self, tracking_id: Option<i32>) {
+        self.feedback_changed |= self.pressed_by != tracking_id;
+        self.pressed_by = tracking_id;
+    }
+
+    /// Follows a finger from pressing the button until releasing it and
+    /// returns the action when released. Sliding off cancels the press.
+    pub fn on_touch(&mut self, event: &MultitouchEvent) -> Option<A> {
+        match *event {
+            MultitouchEvent::Press { finger } if self.pressed_by.is_none() && self.hit(finger.pos).is_some() => {
+                self.set_pressed_by(Some(finger.tracking_id));
+            },
+            MultitouchEvent::Move { finger } if self.pressed_by == Some(finger.tracking_id) && self.hit(finger.pos).is_none() => {
+                self.set_pressed_by(None);
+            },
+            MultitouchEvent::Release { finger } if self.pressed_by == Some(finger.tracking_id) => {
+                self.set_pressed_by(None);
+                return self.hit(finger.pos);
+            },
+            _ => { },
+        }
+        None
+    }
+
+    /// Draws the button again if it got pressed, released, focused or
+    /// unfocused since the last time. Only refreshes the button and as
+    /// fast as possible.
+    pub fn draw_feedback(&mut self, canvas: &mut Canvas) {
+        if self.feedback_changed && self.shown {
+            let rect = self.draw(canvas);
+            canvas.update_partial_mono(&rect);
+        }
+        self.feedback_changed = false;
+    }
+
+    pub fn hit(&self, pos: Point2<u16>) -> Option<A> {
+        if self.shown && self.enabled && Canvas::is_hitting(pos, self.rect()) {
+            Some(self.action)
+        }else {
+            None
+        }
+    }
+}
+
+impl<A: Copy> Widget for Button<A> {
+    fn size(&self) -> Vector2<u32> {
+        let text_size = self.label.size();
+        Vector2 { x: self.hgap + text_size.x + self.hgap, y: self.vgap + text_size.y + self.vgap }
+    }
+
+    fn set_pos(&mut self, pos: Point2<i32>) {
+        self.pos = pos;
+        self.label.set_pos(Point2 { x: pos.x + self.hgap as i32, y: pos.y + self.vgap as i32 });
+    }
+
+    fn draw(&mut self, canvas: &mut Canvas) -> mxcfb_rect {
+        let outer_rect = self.outer_rect();
+        let outer_pos = Point2 { x: outer_rect.left as i32, y: outer_rect.top as i32 };
+        canvas.fill_background(outer_pos, outer_rect.size());
+        if self.focused {
+            canvas.draw_rect(Point2 { x: Some(outer_pos.x), y: Some(outer_pos.y) }, outer_rect.size(), 3);
+        }
+
+        let theme = canvas.theme();
+        if self.pressed_by.is_some() {
+            canvas.set_theme(theme.inverted());
+        }
+        canvas.fill_background(self.pos, self.size());
+        self.label.draw(canvas);
+        let border_px = if self.enabled { 5 } else { 1 };
+        canvas.draw_rect(Point2 { x: Some(self.pos.x), y: Some(self.pos.y) }, self.size(), border_px);
+        canvas.set_theme(theme);
+
+        self.shown = true;
+        self.feedback_changed = false;
+        outer_rect
+    }
+}
+
+impl<A: Copy> Focusable for Button<A> {
+    fn can_focus(&self) -> bool {
+        self.shown && self.enabled
+    }
+
+    fn is_focused(&self) -> bool {
+        self.focused
+    }
+
+    fn set_focused(&mut self, focused: bool) {
+        self.feedback_changed |= self.focused != focused;
+        self.focused = focused;
+    }
+}
+
+/// Button that switches between two texts (e.g. "Pause" and "Resume").
+pub struct Toggle<A> {
+    button: Button<A>,
+    texts: [String; 2],
+}
+
+impl<A: Copy> Toggle<A> {
+    pub fn new(off_text: &str, on_text: &str, font_size: f32, action: A) -> Self {
+        Self {
+            button: Button::new(off_text, font_size, action),
+            texts: [off_text.to_owned(), on_text.to_owned()],
+        }
+    }
+
+    pub fn with_gaps(mut self, vgap: u32, hgap: u32) -> Self {
+        self.button = self.button.with_gaps(vgap, hgap);
+        self
+    }
+
+    /// Only changes the text. Needs to be drawn again to show it.
+    pub fn set_value(&mut self, value: bool) {
+        self.button.set_text(&self.texts[value as usize]);
+    }
+
+    pub fn rect(&self) -> mxcfb_rect {
+        self.button.rect()
+    }
+
+    pub fn on_touch(&mut self, event: &MultitouchEvent) -> Option<A> {
+        self.button.on_touch(event)
+    }
+
+    pub fn draw_feedback(&mut self, canvas: &mut Canvas) {
+        self.button.draw_feedback(canvas);
+    }
+}
+
+impl<A: Copy> Widget for Toggle<A> {
+    fn size(&self) -> Vector2<u32> {
+        self.button.size()
+    }
+
+    fn set_pos(&mut self, pos: Point2<i32>) {
+        self.button.set_pos(pos);
+    }
+
+    fn draw(&mut self, canvas: &mut Canvas) -> mxcfb_rect {
+        self.button.draw(canvas)
+    }
+}
+
+/// Description of a value with "-" and "+" buttons to the right.
+pub struct Stepper<A> {
+    label: Label,
+    minus: Button<i32>,
+    plus: Button<i32>,
+    action: A,
+}
+
+impl<A: Copy> Stepper<A> {
+    pub fn new(text: &str, font_size: f32, action: A) -> Self {
+        Self {
+            label: Label::new(text, font_size),
+            minus: Button::new("-", font_size * 1.5, -1).with_gaps(10, 30),
+            plus: Button::new("+", font_size * 1.5, 1).with_gaps(10, 30),
+            action,
+        }
+    }
+
+    pub fn set_text(&mut self, text: &str) {
+        self.label.set_text(text);
+    }
+
+    /// Puts the text at the left and the buttons at buttons_x.
+    pub fn set_row(&mut self, left: i32, top: i32, buttons_x: i32) {
+        row(&mut [&mut self.minus, &mut self.plus], Some(buttons_x), top, 80);
+        let text_top = top + (self.minus.size().y as i32 - self.label.size().y as i32) / 2;
+        self.label.set_pos(Point2 { x: left, y: text_top });
+    }
+
+    /// Draws only the text again (after it changed).
+    /// Returns the area that needs to be refreshed.
+    pub fn redraw_text(&mut self, canvas: &mut Canvas) -> mxcfb_rect {
+        let text_rect = self.label.rect();
+        let rect = mxcfb_rect {
+            width: (self.minus.rect().left - 10).saturating_sub(text_rect.left),
+            ..text_rect
+        };
+        canvas.fill_background(Point2 { x: rect.left as i32, y: rect.top as i32 }, rect.size());
+        self.label.draw(canvas);
+        rect
+    }
+
+    /// Follows a finger like Button::on_touch(). Returns the
+    /// action and the step (-1 or 1) of the button that was released.
+    pub fn on_touch(&mut self, event: &MultitouchEvent) -> Option<(A, i32)> {
+        let minus = self.minus.on_touch(event);
+        let plus = self.plus.on_touch(event);
+        minus.or(plus).map(|step| (self.action, step))
+    }
+
+    /// The action and the step of the focused button.
+    pub fn activate(&self) -> Option<(A, i32)> {
+        self.minus.activate().or_else(|| self.plus.activate()).map(|step| (self.action, step))
+    }
+
+    pub fn draw_feedback(&mut self, canvas: &mut Canvas) {
+        self.minus.draw_feedback(canvas);
+        self.plus.draw_feedback(canvas);
+    }
+
+    pub fn buttons_mut(&mut self) -> [&mut Button<i32>; 2] {
+        [&mut self.minus, &mut self.plus]
+    }
+
+    pub fn draw(&mut self, canvas: &mut Canvas) -> mxcfb_rect {
+        draw_all(&mut [&mut self.minus, &mut self.plus], canvas).unwrap().merge_rect(&self.redraw_text(canvas))
+    }
+}
+
+/// What the player did in a SettingsList.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SettingsEvent<S> {
+    /// The value of the setting should go one step (-1 or 1)
+    Step(S, i32),
+    Save,
+    Back,
+}
+
+/// A Stepper per setting and "Save" and "Back" buttons at the bottom.
+/// The settings themselves are up to the scene using it.
+pub struct SettingsList<S> {
+    settings: Vec<S>,
+    rows: Vec<Stepper<S>>,
+    save_button: Button<SettingsEvent<S>>,
+    back_button: Button<SettingsEvent<S>>,
+    /// Settings stepped since the last draw
+    changed: Vec<S>,
+    top: i32,
+    gap: i32,
+    buttons_x: i32,
+}
+
+impl<S: Copy + PartialEq> SettingsList<S> {
+    const FONT_SIZE: f32 = 50.0;
+
+    /// Rows start at `top` with `gap` between them. The
+    /// "-" and "+" buttons of all rows are at `buttons_x`.
+    pub fn new(settings: &[S], top: i32, gap: i32, buttons_x: i32) -> Self {
+        Self {
+            settings: settings.to_vec(),
+            rows: settings.iter().map(|setting| Stepper::new("", Self::FONT_SIZE, *setting)).collect(),
+            save_button: Button::new("Save", 100.0, SettingsEvent::Save),
+            back_button: Button::new("Back", 100.0, SettingsEvent::Back),
+            changed: vec![],
+            top,
+            gap,
+            buttons_x,
+        }
+    }
+
+    /// Top of the row with the given index. Also works
+    /// past the last one to put something below.
+    pub fn row_y(&self, index: usize) -> i32 {
+        self.top + index as i32 * self.gap
+    }
+
+    /// Draws all rows with the text `describe` gives for
+    /// their setting, and the buttons at the bottom.
+    pub fn draw(&mut self, canvas: &mut Canvas, describe: impl Fn(S) -> String) {
+        self.changed.clear();
+        for index in 0..self.rows.len() {
+            let top = self.row_y(index);
+            let row = &mut self.rows[index];
+            row.set_text(&describe(self.settings[index]));
+            row.set_row(100, top, self.buttons_x);
+            row.draw(canvas);
+        }
+        self.save_button.set_pos(Point2 { x: 200, y: 1780 });
+        self.back_button.set_pos(Point2 { x: 900, y: 1780 });
+        draw_all(&mut [&mut self.save_button, &mut self.back_button], canvas);
+    }
+
+    /// Redraws the text of the settings that got stepped and
+    /// the feedback of the buttons. Refreshes what changed.
+    pub fn draw_changes(&mut self, canvas: &mut Canvas, describe: impl Fn(S) -> String) {
+        for setting in std::mem::take(&mut self.changed) {
+            if let Some(index) = self.settings.iter().position(|s| *s == setting) {
+                self.rows[index].set_text(&describe(setting));
+                let rect = self.rows[index].redraw_text(canvas);
+                canvas.update_partial(&rect);
+            }
+        }
+        for row in self.rows.iter_mut() {
+            row.draw_feedback(canvas);
+        }
+        self.save_button.draw_feedback(canvas);
+        self.back_button.draw_feedback(canvas);
+    }
+
+    /// Follows the fingers and the hardware buttons.
+    pub fn on_input(&mut self, event: &InputEvent) -> Option<SettingsEvent<S>> {
+        let result = match event {
+            InputEvent::MultitouchEvent { event } => {
+                // Every button needs to see the event
+                let step = self.rows.iter_mut().fold(None, |hit, row| row.on_touch(event).or(hit));
+                let save = self.save_button.on_touch(event);
+                let back = self.back_button.on_touch(event);
+                step.map(|(setting, step)| SettingsEvent::Step(setting, step)).or(save).or(back)
+            },
+            _ => match navigation(event) {
+                Some(Navigation::Move(step)) => {
+                    move_focus(&mut self.focusables(), step);
+                    None
+                },
+                Some(Navigation::Activate) => self.rows.iter()
+                    .find_map(|row| row.activate())
+                    .map(|(setting, step)| SettingsEvent::Step(setting, step))
+                    .or_else(|| self.save_button.activate())
+                    .or_else(|| self.back_button.activate()),
+                None => None,
+            },
+        };
+        if let Some(SettingsEvent::Step(setting, _)) = result {
+            if !self.changed.contains(&setting) {
+                self.changed.push(setting);
+            }
+        }
+        result
+    }
+
+    fn focusables(&mut self) -> Vec<&mut dyn Focusable> {
+        let mut focusables: Vec<&mut dyn Focusable> = vec![];
+        for row in self.rows.iter_mut() {
+            for button in row.buttons_mut() {
+                focusables.push(button);
+            }
+        }
+        focusables.push(&mut self.save_button);
+        focusables.push(&mut self.back_button);
+        focusables
+    }
+}
+
+/// Buttons below each other, e.g. the choices of a menu.
+pub struct List<A> {
+    buttons: Vec<Button<A>>,
+    gap: u32,
+    pos: Point2<i32>,
+}
+
+impl<A: Copy> List<A> {
+    pub fn new(gap: u32) -> Self {
+        Self { buttons: vec![], gap, pos: Point2 { x: 0, y: 0 } }
+    }
+
+    pub fn with_button(mut self, button: Button<A>) -> Self {
+        self.buttons.push(button);
+        self
+    }
+
+    pub fn buttons_mut(&mut self) -> &mut [Button<A>] {
+        &mut self.buttons
+    }
+
+    /// Can't be hit anymore until it gets drawn again.
+    pub fn hide(&mut self) {
+        for button in self.buttons.iter_mut() {
+            button.hide();
+        }
+    }
+
+    pub fn on_touch(&mut self, event: &MultitouchEvent) -> Option<A> {
+        // Every button needs to see the event
+        self.buttons.iter_mut().fold(None, |action, button| button.on_touch(event).or(action))
+    }
+
+    /// The action of the focused button.
+    pub fn activate(&self) -> Option<A> {
+        self.buttons.iter().find_map(|button| button.activate())
+    }
+
+    pub fn focusables(&mut self) -> Vec<&mut dyn Focusable> {
+        self.buttons.iter_mut().map(|button| button as &mut dyn Focusable).collect()
+    }
+
+    pub fn draw_feedback(&mut self, canvas: &mut Canvas) {
+        for button in self.buttons.iter_mut() {
+            button.draw_feedback(canvas);
+        }
+    }
+}
+
+impl<A: Copy> Widget for List<A> {
+    fn size(&self) -> Vector2<u32> {
+        Vector2 {
+            x: self.buttons.iter().map(|button| button.size().x).max().unwrap_or(0),
+            y: self.buttons.iter().map(|button| button.size().y).sum::<u32>() + self.gap * self.buttons.len().saturating_sub(1) as u32,
+        }
+    }
+
+    /// The buttons are centered inside the list.
+    fn set_pos(&mut self, pos: Point2<i32>) {
+        self.pos = pos;
+        let width = self.size().x;
+        let mut y = pos.y;
+        for button in self.buttons.iter_mut() {
+            let size = button.size();
+            button.set_pos(Point2 { x: pos.x + (width - size.x) as i32 / 2, y });
+            y += (size.y + self.gap) as i32;
+        }
+    }
+
+    fn draw(&mut self, canvas: &mut Canvas) -> mxcfb_rect {
+        let rect = rect_at(self.pos, self.size());
+        self.buttons.iter_mut()
+            .map(|button| button.draw(canvas))
+            .fold(rect, |all, rect| all.merge_rect(&rect))
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,206 @@
+use super::*;
+use libremarkable::input::Finger;
+
+fn center(rect: mxcfb_rect) -> Point2<u16> {
+    Point2 { x: (rect.left + rect.width / 2) as u16, y: (rect.top + rect.height / 2) as u16 }
+}
+
+/// Like drawing it but without a framebuffer.
+fn show<A: Copy>(button: &mut Button<A>) {
+    button.shown = true;
+}
+
+#[test]
+fn button_hit_only_when_shown_and_enabled() {
+    let mut button = Button::new("Easy", 125.0, 1);
+    button.set_pos(Point2 { x: 100, y: 200 });
+    let pos = center(button.rect());
+    assert_eq!(button.hit(pos), None);
+
+    show(&mut button);
+    assert_eq!(button.hit(pos), Some(1));
+    assert_eq!(button.hit(Point2 { x: 50, y: 50 }), None);
+
+    button.set_enabled(false);
+    assert_eq!(button.hit(pos), None);
+    button.set_enabled(true);
+    button.hide();
+    assert_eq!(button.hit(pos), None);
+}
+
+#[test]
+fn row_places_next_to_each_other() {
+    let mut first = Button::new("Pause", 50.0, 0).with_gaps(10, 20);
+    let mut second = Button::new("Main Menu", 50.0, 1).with_gaps(10, 20);
+    row(&mut [&mut first, &mut second], Some(25), 30, 50);
+    assert_eq!(first.rect().left, 25);
+    assert_eq!(first.rect().top, 30);
+    assert_eq!(second.rect().left, first.rect().left + first.rect().width + 50);
+    assert_eq!(second.rect().top, 30);
+}
+
+#[test]
+fn row_centers_without_x() {
+    let mut first = Button::new("Versus", 125.0, 0);
+    let mut second = Button::new("Puzzles", 125.0, 1);
+    row(&mut [&mut first, &mut second], None, 0, 100);
+    let left_space = first.rect().left;
+    let right_space = DISPLAYWIDTH as u32 - (second.rect().left + second.rect().width);
+    assert!(left_space.abs_diff(right_space) <= 1);
+}
+
+#[test]
+fn list_centers_buttons_below_each_other() {
+    let mut list = List::new(30)
+        .with_button(Button::new("Resume", 60.0, 0))
+        .with_button(Button::new("Quit to menu", 60.0, 1));
+    column(&mut [&mut list], None, 500, 0);
+    let buttons = list.buttons_mut();
+    let (first, second) = (buttons[0].rect(), buttons[1].rect());
+    assert_eq!(first.top, 500);
+    assert_eq!(second.top, first.top + first.height + 30);
+    assert!((first.left + first.width / 2).abs_diff(second.left + second.width / 2) <= 1);
+    assert!(second.width > first.width);
+}
+
+#[test]
+fn toggle_changes_text() {
+    let mut toggle = Toggle::new("Pause", "Resume", 50.0, 0);
+    let width = toggle.size().x;
+    toggle.set_value(true);
+    assert!(toggle.size().x > width);
+}
+
+#[test]
+fn stepper_reports_step() {
+    let mut stepper = Stepper::new("Ghost piece: Off", 50.0, 7);
+    stepper.set_row(100, 500, 970);
+    show(&mut stepper.minus);
+    show(&mut stepper.plus);
+    let tap = |stepper: &mut Stepper<i32>, pos| {
+        stepper.on_touch(&MultitouchEvent::Press { finger: finger(0, pos) });
+        stepper.on_touch(&MultitouchEvent::Release { finger: finger(0, pos) })
+    };
+    let (minus, plus, label) = (center(stepper.minus.rect()), center(stepper.plus.rect()), center(stepper.label.rect()));
+    assert_eq!(tap(&mut stepper, minus), Some((7, -1)));
+    assert_eq!(tap(&mut stepper, plus), Some((7, 1)));
+    assert_eq!(tap(&mut stepper, label), None);
+}
+
+fn finger(tracking_id: i32, pos: Point2<u16>) -> Finger {
+    let mut finger = Finger::default();
+    finger.tracking_id = tracking_id;
+    finger.pos = pos;
+    finger
+}
+
+#[test]
+fn button_triggers_on_release() {
+    let mut button = Button::new("Easy", 125.0, 1);
+    button.set_pos(Point2 { x: 100, y: 200 });
+    show(&mut button);
+    let pos = center(button.rect());
+
+    assert_eq!(button.on_touch(&MultitouchEvent::Press { finger: finger(3, pos) }), None);
+    assert_eq!(button.pressed_by, Some(3));
+    assert!(button.feedback_changed);
+    // Other fingers don't count
+    assert_eq!(button.on_touch(&MultitouchEvent::Release { finger: finger(4, pos) }), None);
+    assert_eq!(button.on_touch(&MultitouchEvent::Release { finger: finger(3, pos) }), Some(1));
+    assert_eq!(button.pressed_by, None);
+}
+
+#[test]
+fn sliding_off_cancels_press() {
+    let mut button = Button::new("Easy", 125.0, 1);
+    button.set_pos(Point2 { x: 100, y: 200 });
+    show(&mut button);
+    let pos = center(button.rect());
+    let outside = Point2 { x: 50, y: 50 };
+
+    button.on_touch(&MultitouchEvent::Press { finger: finger(3, pos) });
+    button.on_touch(&MultitouchEvent::Move { finger: finger(3, outside) });
+    assert_eq!(button.pressed_by, None);
+    // Coming back doesn't press it again
+    button.on_touch(&MultitouchEvent::Move { finger: finger(3, pos) });
+    assert_eq!(button.on_touch(&MultitouchEvent::Release { finger: finger(3, pos) }), None);
+}
+
+#[test]
+fn list_reports_released_button() {
+    let mut list = List::new(30)
+        .with_button(Button::new("Resume", 60.0, 0))
+        .with_button(Button::new("Quit to menu", 60.0, 1));
+    column(&mut [&mut list], None, 500, 0);
+    list.buttons_mut().iter_mut().for_each(show);
+    let pos = center(list.buttons_mut()[1].rect());
+
+    assert_eq!(list.on_touch(&MultitouchEvent::Press { finger: finger(0, pos) }), None);
+    assert_eq!(list.on_touch(&MultitouchEvent::Release { finger: finger(0, pos) }), Some(1));
+}
+
+#[test]
+fn focus_wraps_and_skips_disabled() {
+    let mut buttons: Vec<Button<i32>> = (0..3).map(|action| Button::new("Easy", 60.0, action)).collect();
+    buttons.iter_mut().for_each(show);
+    buttons[1].set_enabled(false);
+    let focused = |buttons: &[Button<i32>]| buttons.iter().position(|button| button.is_focused());
+    let focus = |buttons: &mut Vec<Button<i32>>, step| {
+        let mut widgets: Vec<&mut dyn Focusable> = buttons.iter_mut().map(|button| button as &mut dyn Focusable).collect();
+        move_focus(&mut widgets, step);
+    };
+
+    focus(&mut buttons, 1);
+    assert_eq!(focused(&buttons), Some(0));
+    focus(&mut buttons, 1);
+    assert_eq!(focused(&buttons), Some(2));
+    focus(&mut buttons, 1);
+    assert_eq!(focused(&buttons), Some(0));
+    focus(&mut buttons, -1);
+    assert_eq!(focused(&buttons), Some(2));
+    assert!(buttons[2].feedback_changed);
+}
+
+#[test]
+fn focus_starts_at_the_end_when_going_back() {
+    let mut list = List::new(30)
+        .with_button(Button::new("Resume", 60.0, 0))
+        .with_button(Button::new("Quit to menu", 60.0, 1));
+    list.buttons_mut().iter_mut().for_each(show);
+    assert_eq!(list.activate(), None);
+    move_focus(&mut list.focusables(), -1);
+    assert_eq!(list.activate(), Some(1));
+
+    list.hide();
+    assert_eq!(list.activate(), None);
+    move_focus(&mut list.focusables(), 1);
+    assert_eq!(list.activate(), None);
+}
+
+#[test]
+fn settings_list_reports_steps_and_footer() {
+    let mut list = SettingsList::new(&['a', 'b'], 480, 140, 970);
+    for row in list.rows.iter_mut() {
+        for button in row.buttons_mut() {
+            show(button);
+        }
+    }
+    show(&mut list.save_button);
+    show(&mut list.back_button);
+    let next = InputEvent::GPIO { event: GPIOEvent::Press { button: PhysicalButton::RIGHT } };
+    let activate = InputEvent::GPIO { event: GPIOEvent::Press { button: PhysicalButton::MIDDLE } };
+
+    assert_eq!(list.on_input(&activate), None);
+    assert_eq!(list.on_input(&next), None);
+    assert_eq!(list.on_input(&activate), Some(SettingsEvent::Step('a', -1)));
+    assert_eq!(list.on_input(&activate), Some(SettingsEvent::Step('a', -1)));
+    assert_eq!(list.changed, vec!['a']);
+    for _ in 0..4 {
+        list.on_input(&next);
+    }
+    assert_eq!(list.on_input(&activate), Some(SettingsEvent::Save));
+    list.on_input(&next);
+    assert_eq!(list.on_input(&activate), Some(SettingsEvent::Back));
+    assert_eq!(list.changed, vec!['a']);
+    assert_eq!(list.row_y(2), 760);
+}